@@ -2,17 +2,19 @@ use macroquad::prelude::*;
 use rapier2d::{dynamics::RigidBodyBuilder, geometry::ColliderBuilder, pipeline::PhysicsPipeline};
 use shipyard::{AllStoragesViewMut, UniqueViewMut, World};
 use shipyard_rapier2d::{
-    physics::{
-        create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
-        setup_physics, step_world_system,
-    },
-    render::{render_colliders, render_physics_stats},
+    physics::{PhysicsDeltaTime, PhysicsPlugin, PhysicsPluginConfig, PhysicsSetup},
+    render::{render_colliders, render_physics_metrics, render_physics_stats},
 };
 
 #[macroquad::main("Boxes 2D")]
 async fn main() {
     let world = World::new();
-    world.run(setup_physics).unwrap();
+    PhysicsPlugin::new(PhysicsPluginConfig {
+        setup: PhysicsSetup::new().metrics(true),
+        ..Default::default()
+    })
+    .build(&world)
+    .unwrap();
     world.run(setup_physics_world).unwrap();
 
     let viewport_height = 120.0;
@@ -20,10 +22,7 @@ async fn main() {
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(0.0, -50.0),
         ..Default::default()
     };
@@ -35,17 +34,14 @@ async fn main() {
         set_camera(camera);
 
         // Systems to update physics world
-        world.run(create_body_and_collider_system).unwrap();
-        world.run(create_joints_system).unwrap();
-        world
-            .run_with_data(step_world_system, get_frame_time())
-            .unwrap();
-        world.run(destroy_body_and_collider_system).unwrap();
+        world.borrow::<UniqueViewMut<PhysicsDeltaTime>>().unwrap().0 = get_frame_time();
+        world.run_workload(PhysicsPlugin::WORKLOAD_NAME).unwrap();
 
         world.run(render_colliders).unwrap();
 
         set_default_camera();
         world.run(render_physics_stats).unwrap();
+        world.run(render_physics_metrics).unwrap();
 
         next_frame().await
     }
@@ -2,29 +2,45 @@ use macroquad::prelude::*;
 use rapier2d::{
     dynamics::RigidBodyBuilder,
     geometry::{ColliderBuilder, SolverFlags},
-    pipeline::{PhysicsPipeline, PhysicsHooks, PhysicsHooksFlags, PairFilterContext},
+    pipeline::{PairFilterContext, PhysicsHooksFlags, PhysicsPipeline},
 };
-use shipyard::{AllStoragesViewMut, UniqueViewMut, World};
+use shipyard::{error, AllStorages, AllStoragesViewMut, EntityId, Get, UniqueViewMut, View, World};
 use shipyard_rapier2d::{
     physics::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
-        setup_physics, step_world_system, UserPhysicsHooks,
+        setup_physics, step_world_with_entity_hooks_system, EntityPhysicsHooks, UserPhysicsHooks,
     },
     render::{render_colliders, render_physics_stats},
 };
 
-// A custom filter that allows contacts only between rigid-bodies with the
-// same user_data value.
+// The group of a rigid-body, only rigid-bodies of the same group touch each other.
+#[derive(PartialEq)]
+struct Group(u32);
+
+// A custom filter that allows contacts only between entities with the
+// same `Group` component.
 // Note that using collision groups would be a more efficient way of doing
 // this, but we use custom filters instead for demonstration purpose.
-struct SameUserDataFilter;
-impl PhysicsHooks for SameUserDataFilter {
+struct SameGroupFilter;
+impl EntityPhysicsHooks for SameGroupFilter {
+    type Views<'a> = View<'a, Group>;
+
+    fn borrow_views(all_storages: &AllStorages) -> Result<View<'_, Group>, error::GetStorage> {
+        all_storages.borrow::<View<Group>>()
+    }
+
     fn active_hooks(&self) -> PhysicsHooksFlags {
         PhysicsHooksFlags::FILTER_CONTACT_PAIR
     }
 
-    fn filter_contact_pair(&self, context: &PairFilterContext) -> Option<SolverFlags> {
-        if context.rigid_body1.user_data == context.rigid_body2.user_data {
+    fn filter_contact_pair(
+        &self,
+        groups: &View<Group>,
+        entity1: EntityId,
+        entity2: EntityId,
+        _context: &PairFilterContext,
+    ) -> Option<SolverFlags> {
+        if groups.get(entity1).ok() == groups.get(entity2).ok() {
             Some(SolverFlags::COMPUTE_IMPULSES)
         } else {
             None
@@ -43,10 +59,7 @@ async fn main() {
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(0.0, 0.0),
         ..Default::default()
     };
@@ -61,7 +74,8 @@ async fn main() {
         world.run(create_body_and_collider_system).unwrap();
         world.run(create_joints_system).unwrap();
         world
-            .run_with_data(step_world_system, get_frame_time())
+            .run_with_data(step_world_with_entity_hooks_system, get_frame_time())
+            .unwrap()
             .unwrap();
         world.run(destroy_body_and_collider_system).unwrap();
 
@@ -86,20 +100,18 @@ pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
         let mut user_hooks = all_storages
             .borrow::<UniqueViewMut<UserPhysicsHooks>>()
             .unwrap();
-        user_hooks.hooks(SameUserDataFilter);
+        user_hooks.entity_hooks(SameGroupFilter);
     }
 
     let ground_size = 10.0;
 
-    let rigid_body = RigidBodyBuilder::new_static()
-        .translation(0.0, -10.0)
-        .user_data(0);
+    let rigid_body = RigidBodyBuilder::new_static().translation(0.0, -10.0);
     let collider = ColliderBuilder::cuboid(ground_size, 1.2);
-    all_storages.add_entity((rigid_body, collider));
+    all_storages.add_entity((rigid_body, collider, Group(0)));
 
-    let rigid_body = RigidBodyBuilder::new_static().user_data(1);
+    let rigid_body = RigidBodyBuilder::new_static();
     let collider = ColliderBuilder::cuboid(ground_size, 1.2);
-    all_storages.add_entity((rigid_body, collider));
+    all_storages.add_entity((rigid_body, collider, Group(1)));
 
     /*
      * Create the cubes
@@ -117,11 +129,9 @@ pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
             let y = j as f32 * shift + centery + 2.0;
 
             // Build the rigid body.
-            let body = RigidBodyBuilder::new_dynamic()
-                .user_data(j as u128 % 2)
-                .translation(x, y);
+            let body = RigidBodyBuilder::new_dynamic().translation(x, y);
             let collider = ColliderBuilder::cuboid(rad, rad).density(1.0);
-            all_storages.add_entity((body, collider));
+            all_storages.add_entity((body, collider, Group(j as u32 % 2)));
         }
     }
 }
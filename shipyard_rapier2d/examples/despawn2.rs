@@ -24,10 +24,7 @@ async fn main() {
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(0.0, -50.0),
         ..Default::default()
     };
@@ -20,10 +20,7 @@ async fn main() {
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(0.0, 0.0),
         ..Default::default()
     };
@@ -60,6 +57,10 @@ fn display_events(events: UniqueViewMut<EventQueue>) {
     while let Ok(contact_event) = events.contact_events.pop() {
         println!("Received contact event: {:?}", contact_event);
     }
+
+    while let Ok(collision_event) = events.collision_events.pop() {
+        println!("Received collision event: {:?}", collision_event);
+    }
 }
 
 fn enable_physics_profiling(mut pipeline: UniqueViewMut<PhysicsPipeline>) {
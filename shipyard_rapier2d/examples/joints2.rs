@@ -1,8 +1,8 @@
 use macroquad::prelude::*;
 use rapier2d::{
-    na::Point2,
     dynamics::{BallJoint, BodyStatus, RigidBodyBuilder},
     geometry::ColliderBuilder,
+    na::Point2,
     pipeline::PhysicsPipeline,
 };
 use shipyard::{AllStoragesViewMut, UniqueViewMut, World};
@@ -11,12 +11,18 @@ use shipyard_rapier2d::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
         setup_physics, step_world_system, JointBuilderComponent,
     },
-    render::{render_colliders, render_physics_stats},
+    render::{render_colliders, render_joints, render_physics_stats, DebugRenderSettings},
 };
 
 #[macroquad::main("Joints 2D")]
 async fn main() {
     let world = World::new();
+    world
+        .add_unique(DebugRenderSettings {
+            joints: true,
+            ..Default::default()
+        })
+        .unwrap();
     world.run(setup_physics).unwrap();
     world.run(setup_physics_world).unwrap();
 
@@ -25,10 +31,7 @@ async fn main() {
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(20.0, 20.0),
         ..Default::default()
     };
@@ -48,6 +51,7 @@ async fn main() {
         world.run(destroy_body_and_collider_system).unwrap();
 
         world.run(render_colliders).unwrap();
+        world.run(render_joints).unwrap();
 
         set_default_camera();
         world.run(render_physics_stats).unwrap();
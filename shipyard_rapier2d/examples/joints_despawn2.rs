@@ -1,8 +1,8 @@
 use macroquad::prelude::*;
 use rapier2d::{
-    na::Point2,
     dynamics::{BallJoint, BodyStatus, RigidBodyBuilder},
     geometry::ColliderBuilder,
+    na::Point2,
     pipeline::PhysicsPipeline,
 };
 use shipyard::{AllStoragesViewMut, EntityId, UniqueViewMut, World};
@@ -14,7 +14,7 @@ use shipyard_rapier2d::{
             destroy_body_and_collider_system, setup_physics, step_world_system,
         },
     },
-    render::{render_colliders, render_physics_stats},
+    render::{render_colliders, render_joints, render_physics_stats, DebugRenderSettings},
 };
 
 pub struct DespawnResource {
@@ -24,6 +24,12 @@ pub struct DespawnResource {
 #[macroquad::main("Joints Despawn 2D")]
 async fn main() {
     let world = World::new();
+    world
+        .add_unique(DebugRenderSettings {
+            joints: true,
+            ..Default::default()
+        })
+        .unwrap();
     world.run(setup_physics).unwrap();
     world.run(setup_physics_world).unwrap();
 
@@ -32,10 +38,7 @@ async fn main() {
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(20.0, 20.0),
         ..Default::default()
     };
@@ -56,6 +59,7 @@ async fn main() {
 
         world.run_with_data(despawn, get_time()).unwrap();
         world.run(render_colliders).unwrap();
+        world.run(render_joints).unwrap();
 
         set_default_camera();
         world.run(render_physics_stats).unwrap();
@@ -1,17 +1,10 @@
 use macroquad::prelude::*;
-use rapier2d::{
-    dynamics::{RigidBodyBuilder, RigidBodySet},
-    geometry::{ColliderBuilder, ColliderSet},
-    pipeline::PhysicsPipeline,
-};
-use shipyard::{
-    AllStoragesViewMut, EntitiesView, EntityId, Get, IntoIter, IntoWithId, UniqueViewMut, View,
-    ViewMut, World,
-};
+use rapier2d::{dynamics::RigidBodyBuilder, geometry::ColliderBuilder, pipeline::PhysicsPipeline};
+use shipyard::{AllStoragesViewMut, UniqueViewMut, World};
 use shipyard_rapier2d::{
     physics::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
-        setup_physics, step_world_system, ColliderHandleComponent, RigidBodyHandleComponent,
+        setup_physics, step_world_system, ColliderParent,
     },
     render::{render_colliders, render_physics_stats},
 };
@@ -27,10 +20,7 @@ async fn main() {
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(0.0, -50.0),
         ..Default::default()
     };
@@ -49,9 +39,6 @@ async fn main() {
             .unwrap();
         world.run(destroy_body_and_collider_system).unwrap();
 
-        // Custom system to create colliders for entities with parents
-        world.run(create_child_collider_system).unwrap();
-
         world.run(render_colliders).unwrap();
 
         set_default_camera();
@@ -96,7 +83,7 @@ pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
             // Build the rigid body.
             let rigid_body = RigidBodyBuilder::new_dynamic().translation(x, y);
 
-            // Attach multiple colliders to this rigid-body using Bevy hierarchy.
+            // Attach multiple colliders to this rigid-body using ColliderParent components.
             let collider1 = ColliderBuilder::cuboid(rad * 10.0, rad);
             let collider2 =
                 ColliderBuilder::cuboid(rad, rad * 10.0).translation(rad * 10.0, rad * 10.0);
@@ -105,40 +92,11 @@ pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
 
             let parent = all_storages.add_entity((rigid_body,));
 
-            all_storages.add_entity((collider1, Child { parent }));
-            all_storages.add_entity((collider2, Child { parent }));
-            all_storages.add_entity((collider3, Child { parent }));
+            all_storages.add_entity((collider1, ColliderParent(parent)));
+            all_storages.add_entity((collider2, ColliderParent(parent)));
+            all_storages.add_entity((collider3, ColliderParent(parent)));
         }
 
         offset -= 0.05 * rad * (num as f32 - 1.0);
     }
 }
-
-#[derive(Debug, Clone)]
-pub struct Child {
-    parent: EntityId,
-}
-
-pub fn create_child_collider_system(
-    entities: EntitiesView,
-    mut bodies: UniqueViewMut<RigidBodySet>,
-    mut colliders: UniqueViewMut<ColliderSet>,
-    mut collider_builders: ViewMut<ColliderBuilder>,
-    mut collider_handles: ViewMut<ColliderHandleComponent>,
-    body_handles: View<RigidBodyHandleComponent>,
-    childs: View<Child>,
-) {
-    for (entity_id, (child, collider_builder)) in (&childs, &collider_builders).iter().with_id() {
-        if let Ok(body_handle) = body_handles.get(child.parent) {
-            let handle =
-                colliders.insert(collider_builder.build(), body_handle.handle(), &mut bodies);
-            entities.add_component(
-                entity_id,
-                &mut collider_handles,
-                ColliderHandleComponent::from(handle),
-            );
-        }
-    }
-
-    collider_builders.clear();
-}
@@ -1,18 +1,17 @@
 use macroquad::prelude::*;
-use rapier2d::{
-    na::Vector2,
-    dynamics::{RigidBodyBuilder, RigidBodySet},
-    geometry::ColliderBuilder,
-};
-use shipyard::{AllStoragesViewMut, IntoIter, UniqueView, UniqueViewMut, View, World};
+use rapier2d::{dynamics::RigidBodyBuilder, geometry::ColliderBuilder, na::Vector2};
+use shipyard::{AllStoragesViewMut, IntoIter, View, ViewMut, World};
 use shipyard_rapier2d::{
     physics::{
-        create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
-        setup_physics, step_world_system, RapierConfiguration, RigidBodyHandleComponent,
+        apply_physics_changes_system, create_body_and_collider_system, create_joints_system,
+        destroy_body_and_collider_system, step_world_system, sync_transforms_system, PhysicsSetup,
+        PhysicsTransform, Velocity,
     },
     render::{render_colliders, render_physics_stats, RapierRenderColor},
 };
 
+const PHYSICS_SCALE: f32 = 20.0;
+
 #[derive(Debug, Default)]
 struct Player {
     speed: f32,
@@ -20,18 +19,21 @@ struct Player {
 
 #[macroquad::main("Player movement 2D")]
 async fn main() {
+    // While we want our sprite to look ~40 px square, we want to keep the physics units smaller
+    // to prevent float rounding problems. To do this, we set the scale factor in RapierConfiguration
+    // and divide our sprite_size by the scale.
     let world = World::new();
-    world.run(setup_physics).unwrap();
+    PhysicsSetup::new()
+        .gravity(Vector2::zeros())
+        .scale(PHYSICS_SCALE)
+        .install(&world);
 
     let viewport_height = 1200.0;
     let aspect = screen_width() / screen_height();
     let viewport_width = viewport_height * aspect;
 
     let camera = Camera2D {
-        zoom: vec2(
-            1.0 / viewport_width as f32 * 2.,
-            -1.0 / viewport_height as f32 * 2.,
-        ),
+        zoom: vec2(1.0 / viewport_width * 2., -1.0 / viewport_height * 2.),
         target: vec2(0.0, 0.0),
         ..Default::default()
     };
@@ -48,10 +50,12 @@ async fn main() {
         world.run(create_joints_system).unwrap();
 
         world.run(player_movement).unwrap();
+        world.run(apply_physics_changes_system).unwrap();
 
         world
             .run_with_data(step_world_system, get_frame_time())
             .unwrap();
+        world.run(sync_transforms_system).unwrap();
         world.run(destroy_body_and_collider_system).unwrap();
 
         world.run(render_colliders).unwrap();
@@ -84,17 +88,7 @@ pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
 }
 
 fn spawn_player(mut all_storages: AllStoragesViewMut) {
-    let scale = 20.0;
-    {
-        let mut rapier_config = all_storages
-            .borrow::<UniqueViewMut<RapierConfiguration>>()
-            .unwrap();
-        rapier_config.gravity = Vector2::zeros();
-        // While we want our sprite to look ~40 px square, we want to keep the physics units smaller
-        // to prevent float rounding problems. To do this, we set the scale factor in RapierConfiguration
-        // and divide our sprite_size by the scale.
-        rapier_config.scale = scale;
-    }
+    let scale = PHYSICS_SCALE;
     let sprite_size_x = 40.0;
     let sprite_size_y = 40.0;
 
@@ -108,43 +102,29 @@ fn spawn_player(mut all_storages: AllStoragesViewMut) {
 
     let player = Player { speed: 300.0 };
 
-    all_storages.add_entity((rigid_body, collider, player, color));
+    all_storages.add_entity((rigid_body, collider, player, color, Velocity::default()));
 }
 
-fn player_movement(
-    rapier_parameters: UniqueView<RapierConfiguration>,
-    player: View<Player>,
-    body_handles: View<RigidBodyHandleComponent>,
-    mut rigid_bodies: UniqueViewMut<RigidBodySet>,
-) {
-    for (player, rigid_body_component) in (&player, &body_handles).iter() {
+fn player_movement(player: View<Player>, mut velocities: ViewMut<Velocity>) {
+    for (player, mut velocity) in (&player, &mut velocities).iter() {
         let x_axis = is_key_down(KeyCode::D) as i8 - is_key_down(KeyCode::A) as i8;
         let y_axis = is_key_down(KeyCode::W) as i8 - is_key_down(KeyCode::S) as i8;
 
         let mut move_delta = Vector2::new(x_axis as f32, y_axis as f32);
         if move_delta != Vector2::zeros() {
-            // Note that the RapierConfiguration::Scale factor is also used here to transform
-            // the move_delta from: 'pixels/second' to 'physics_units/second'
-            move_delta /= move_delta.magnitude() * rapier_parameters.scale;
+            move_delta /= move_delta.magnitude();
         }
 
-        // Update the velocity on the rigid_body_component,
-        if let Some(rb) = rigid_bodies.get_mut(rigid_body_component.handle()) {
-            rb.set_linvel(move_delta * player.speed, true);
-        }
+        // The Velocity component is expressed in pixels/second: it is converted to
+        // physics_units/second with the RapierConfiguration::scale factor when applied.
+        velocity.linvel = move_delta * player.speed;
     }
 }
 
-fn render_player_position(
-    player: View<Player>,
-    body_handles: View<RigidBodyHandleComponent>,
-    rigid_bodies: UniqueView<RigidBodySet>,
-) {
-    for (_, rigid_body_component) in (&player, &body_handles).iter() {
-        if let Some(rb) = rigid_bodies.get(rigid_body_component.handle()) {
-            let pos = rb.position();
-            let text = format!("Player: ({}, {})", pos.translation.x, pos.translation.y);
-            draw_text(&text, 10.0, 70.0, 30.0, BLACK);
-        }
+fn render_player_position(player: View<Player>, transforms: View<PhysicsTransform>) {
+    for (_, transform) in (&player, &transforms).iter() {
+        let pos = transform.translation();
+        let text = format!("Player: ({}, {})", pos.x, pos.y);
+        draw_text(&text, 10.0, 70.0, 30.0, BLACK);
     }
 }
@@ -6,7 +6,7 @@ use shipyard_rapier3d::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
         setup_physics, step_world_system,
     },
-    render::{render_colliders, render_physics_stats},
+    render::{render_colliders, render_physics_stats, ColliderMeshCache},
 };
 
 pub struct DespawnResource {
@@ -16,6 +16,7 @@ pub struct DespawnResource {
 #[macroquad::main("Despawn 3D")]
 async fn main() {
     let world = World::new();
+    world.add_unique(ColliderMeshCache::default()).unwrap();
     world.run(setup_physics).unwrap();
     world.run(setup_physics_world).unwrap();
 
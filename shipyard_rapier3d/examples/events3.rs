@@ -6,12 +6,13 @@ use shipyard_rapier3d::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
         setup_physics, step_world_system, EventQueue,
     },
-    render::{render_colliders, render_physics_stats},
+    render::{render_colliders, render_physics_stats, ColliderMeshCache},
 };
 
 #[macroquad::main("Events 3D")]
 async fn main() {
     let world = World::new();
+    world.add_unique(ColliderMeshCache::default()).unwrap();
     world.run(setup_physics).unwrap();
     world.run(setup_physics_world).unwrap();
 
@@ -58,6 +59,10 @@ fn display_events(events: UniqueViewMut<EventQueue>) {
     while let Ok(contact_event) = events.contact_events.pop() {
         println!("Received contact event: {:?}", contact_event);
     }
+
+    while let Ok(collision_event) = events.collision_events.pop() {
+        println!("Received collision event: {:?}", collision_event);
+    }
 }
 
 pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
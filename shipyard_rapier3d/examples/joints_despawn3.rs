@@ -1,10 +1,10 @@
 use macroquad::prelude::*;
 use rapier3d::{
-    na::{Isometry3, Point3, Unit, Vector3},
     dynamics::{
         BallJoint, BodyStatus, FixedJoint, PrismaticJoint, RevoluteJoint, RigidBodyBuilder,
     },
     geometry::ColliderBuilder,
+    na::{Isometry3, Point3, Unit, Vector3},
     pipeline::PhysicsPipeline,
 };
 use shipyard::{AllStoragesViewMut, EntityId, UniqueViewMut, World};
@@ -13,7 +13,10 @@ use shipyard_rapier3d::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
         setup_physics, step_world_system, JointBuilderComponent,
     },
-    render::{render_colliders, render_physics_stats},
+    render::{
+        render_colliders, render_joints, render_physics_stats, ColliderMeshCache,
+        DebugRenderSettings,
+    },
 };
 
 #[derive(Default)]
@@ -24,6 +27,13 @@ pub struct DespawnResource {
 #[macroquad::main("Joints Despawn 3D")]
 async fn main() {
     let world = World::new();
+    world.add_unique(ColliderMeshCache::default()).unwrap();
+    world
+        .add_unique(DebugRenderSettings {
+            joints: true,
+            ..Default::default()
+        })
+        .unwrap();
     world.run(setup_physics).unwrap();
     world.add_unique(DespawnResource::default()).unwrap();
 
@@ -60,6 +70,7 @@ async fn main() {
         world.run(destroy_body_and_collider_system).unwrap();
 
         world.run(render_colliders).unwrap();
+        world.run(render_joints).unwrap();
 
         // Run despawn system
         world.run_with_data(despawn, get_time()).unwrap();
@@ -1,9 +1,6 @@
 use macroquad::prelude::*;
 use rapier3d::{
-    na::Vector3,
-    dynamics::RigidBodyBuilder,
-    geometry::ColliderBuilder,
-    pipeline::PhysicsPipeline
+    dynamics::RigidBodyBuilder, geometry::ColliderBuilder, na::Vector3, pipeline::PhysicsPipeline,
 };
 use shipyard::{AllStoragesViewMut, UniqueViewMut, World};
 use shipyard_rapier3d::{
@@ -11,12 +8,13 @@ use shipyard_rapier3d::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
         setup_physics, step_world_system,
     },
-    render::{render_colliders, render_physics_stats},
+    render::{render_colliders, render_physics_stats, ColliderMeshCache},
 };
 
 #[macroquad::main("Locked Rotation 3D")]
 async fn main() {
     let world = World::new();
+    world.add_unique(ColliderMeshCache::default()).unwrap();
     world.run(setup_physics).unwrap();
     world.run(setup_physics_world).unwrap();
 
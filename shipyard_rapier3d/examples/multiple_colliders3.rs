@@ -1,24 +1,18 @@
 use macroquad::prelude::*;
-use rapier3d::{
-    dynamics::{RigidBodyBuilder, RigidBodySet},
-    geometry::{ColliderBuilder, ColliderSet},
-    pipeline::PhysicsPipeline,
-};
-use shipyard::{
-    AllStoragesViewMut, EntitiesView, EntityId, Get, IntoIter, IntoWithId, UniqueViewMut, View,
-    ViewMut, World,
-};
+use rapier3d::{dynamics::RigidBodyBuilder, geometry::ColliderBuilder, pipeline::PhysicsPipeline};
+use shipyard::{AllStoragesViewMut, UniqueViewMut, World};
 use shipyard_rapier3d::{
     physics::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
-        setup_physics, step_world_system, ColliderHandleComponent, RigidBodyHandleComponent,
+        setup_physics, step_world_system, ColliderParent,
     },
-    render::{render_colliders, render_physics_stats},
+    render::{render_colliders, render_physics_stats, ColliderMeshCache},
 };
 
 #[macroquad::main("Multiple Colliders 3D")]
 async fn main() {
     let world = World::new();
+    world.add_unique(ColliderMeshCache::default()).unwrap();
     world.run(setup_physics).unwrap();
     world.run(setup_physics_world).unwrap();
 
@@ -38,7 +32,6 @@ async fn main() {
         // Systems to update physics world
         world.run(create_body_and_collider_system).unwrap();
         world.run(create_joints_system).unwrap();
-        world.run(create_child_collider_system).unwrap();
         world
             .run_with_data(step_world_system, get_frame_time())
             .unwrap();
@@ -91,7 +84,7 @@ pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
                 // Build the rigid body.
                 let rigid_body = RigidBodyBuilder::new_dynamic().translation(x, y, z);
 
-                // Attach multiple colliders to this rigid-body using Bevy hierarchy.
+                // Attach multiple colliders to this rigid-body using ColliderParent components.
                 let collider1 = ColliderBuilder::cuboid(rad * 10.0, rad, rad);
                 let collider2 = ColliderBuilder::cuboid(rad, rad * 10.0, rad).translation(
                     rad * 10.0,
@@ -104,46 +97,14 @@ pub fn setup_physics_world(mut all_storages: AllStoragesViewMut) {
                     0.0,
                 );
 
-                // NOTE: we need the Transform and GlobalTransform
-                // so that the transform of the entity with a rigid-body
-                // is properly propagated to its children with collider meshes.
                 let parent = all_storages.add_entity((rigid_body,));
 
-                all_storages.add_entity((collider1, Child { parent }));
-                all_storages.add_entity((collider2, Child { parent }));
-                all_storages.add_entity((collider3, Child { parent }));
+                all_storages.add_entity((collider1, ColliderParent(parent)));
+                all_storages.add_entity((collider2, ColliderParent(parent)));
+                all_storages.add_entity((collider3, ColliderParent(parent)));
             }
         }
 
         offset -= 0.05 * rad * (num as f32 - 1.0);
     }
 }
-
-#[derive(Debug, Clone)]
-pub struct Child {
-    parent: EntityId,
-}
-
-pub fn create_child_collider_system(
-    entities: EntitiesView,
-    mut bodies: UniqueViewMut<RigidBodySet>,
-    mut colliders: UniqueViewMut<ColliderSet>,
-    mut collider_builders: ViewMut<ColliderBuilder>,
-    mut collider_handles: ViewMut<ColliderHandleComponent>,
-    body_handles: View<RigidBodyHandleComponent>,
-    childs: View<Child>,
-) {
-    for (entity_id, (child, collider_builder)) in (&childs, &collider_builders).iter().with_id() {
-        if let Ok(body_handle) = body_handles.get(child.parent) {
-            let handle =
-                colliders.insert(collider_builder.build(), body_handle.handle(), &mut bodies);
-            entities.add_component(
-                entity_id,
-                &mut collider_handles,
-                ColliderHandleComponent::from(handle),
-            );
-        }
-    }
-
-    collider_builders.clear();
-}
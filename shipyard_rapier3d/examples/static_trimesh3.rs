@@ -1,9 +1,8 @@
+#![allow(clippy::identity_op)]
+
 use macroquad::prelude::*;
 use rapier3d::{
-    na::Point3,
-    dynamics::RigidBodyBuilder,
-    geometry::ColliderBuilder,
-    pipeline::PhysicsPipeline
+    dynamics::RigidBodyBuilder, geometry::ColliderBuilder, na::Point3, pipeline::PhysicsPipeline,
 };
 use shipyard::{AllStoragesViewMut, EntitiesViewMut, UniqueViewMut, ViewMut, World};
 use shipyard_rapier3d::{
@@ -11,13 +10,14 @@ use shipyard_rapier3d::{
         create_body_and_collider_system, create_joints_system, destroy_body_and_collider_system,
         setup_physics, step_world_system,
     },
-    render::{render_colliders, render_physics_stats},
+    render::{render_colliders, render_physics_stats, ColliderMeshCache},
 };
 use std::f32::consts::TAU;
 
 #[macroquad::main("Static Trimesh 3D")]
 async fn main() {
     let world = World::new();
+    world.add_unique(ColliderMeshCache::default()).unwrap();
     world.run(setup_physics).unwrap();
     world.run(setup_physics_world).unwrap();
 
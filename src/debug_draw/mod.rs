@@ -0,0 +1,1082 @@
+use crate::physics::{
+    ColliderHandleComponent, EntityMaps, InterpolatedTransform, RapierConfiguration,
+};
+use rapier::dynamics::{IntegrationParameters, JointParams, JointSet, RigidBody, RigidBodySet};
+use rapier::geometry::{
+    Collider, ColliderHandle, ColliderSet, HalfSpace, NarrowPhase, Segment, Shape, ShapeType, AABB,
+};
+use rapier::math::{Isometry, Point, Rotation, Translation, Vector, DIM};
+#[cfg(feature = "dim3")]
+use rapier::na::Unit;
+use shipyard::{error, Get, IntoIter, IntoWithId, UniqueView, View, World};
+use std::collections::HashMap;
+
+mod svg;
+
+pub use self::svg::*;
+
+/// A color, with components between 0 and 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebugColor {
+    /// The red component.
+    pub r: f32,
+    /// The green component.
+    pub g: f32,
+    /// The blue component.
+    pub b: f32,
+    /// The opacity, 1 being opaque.
+    pub a: f32,
+}
+
+impl DebugColor {
+    /// Creates a color from its components, between 0 and 1.
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Creates an opaque color from its 8-bit components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+    }
+}
+
+pub(crate) const PALLETE: [DebugColor; 3] = [
+    DebugColor::rgb(0x98, 0xC1, 0xD9),
+    DebugColor::rgb(0x05, 0x3C, 0x5E),
+    DebugColor::rgb(0x1F, 0x7A, 0x8C),
+];
+
+pub(crate) const GROUND_COLOR: DebugColor = DebugColor::rgb(0xF3, 0xD9, 0xB1);
+
+const CONTACT_COLOR: DebugColor = DebugColor::rgb(0xE6, 0x39, 0x46);
+
+const NORMAL_COLOR: DebugColor = DebugColor::rgb(0x2A, 0x9D, 0x8F);
+
+const JOINT_COLOR: DebugColor = DebugColor::rgb(0x83, 0x38, 0xEC);
+
+const BROKEN_JOINT_COLOR: DebugColor = DebugColor::rgb(0xFF, 0x00, 0x6E);
+
+const LIMIT_COLOR: DebugColor = DebugColor::rgb(0xFB, 0x85, 0x00);
+
+const AWAKE_COLOR: DebugColor = DebugColor::rgb(0x2A, 0x9D, 0x56);
+
+const SLEEPING_COLOR: DebugColor = DebugColor::rgb(0x8D, 0x99, 0xAE);
+
+const KINEMATIC_COLOR: DebugColor = DebugColor::rgb(0xFF, 0xB7, 0x03);
+
+const ANGULAR_VELOCITY_COLOR: DebugColor = DebugColor::rgb(0xFB, 0x56, 0x07);
+
+const AABB_COLOR: DebugColor = DebugColor::rgb(0x3A, 0x86, 0xFF);
+
+/// Colors of the x, y and z axes of the joint frames.
+const AXIS_COLORS: [DebugColor; 3] = [
+    DebugColor::rgb(0xFF, 0x00, 0x00),
+    DebugColor::rgb(0x00, 0xFF, 0x00),
+    DebugColor::rgb(0x00, 0x00, 0xFF),
+];
+
+/// The desired render color of a Rapier collider.
+pub struct RapierRenderColor(pub f32, pub f32, pub f32);
+
+/// Which debug overlays are drawn on top of the colliders.
+///
+/// It must be added to the World before running the debug render systems. Every
+/// overlay is disabled by default.
+#[derive(Clone, Copy, Debug)]
+pub struct DebugRenderSettings {
+    /// Draws the contacts of the active contact pairs, see `render_contacts`.
+    pub contacts: bool,
+    /// Length, in Rapier units, of the contact normals per unit of impulse.
+    pub contact_impulse_scale: f32,
+    /// Draws the joints of the `JointSet`, see `render_joints`.
+    pub joints: bool,
+    /// Distance, in Rapier units, the anchors of a joint can drift apart before the
+    /// joint is highlighted as broken.
+    pub joint_error_tolerance: f32,
+    /// Colors the colliders by the state of their rigid-body, see `render_colliders`.
+    pub body_states: bool,
+    /// Draws the AABB of the colliders, see `render_debug_overlays`.
+    pub aabbs: bool,
+    /// Draws the linear and angular velocities of the rigid-bodies.
+    pub velocities: bool,
+    /// Draws the centers of mass of the rigid-bodies.
+    pub centers_of_mass: bool,
+    /// Duration, in seconds, of the motion drawn for the velocities.
+    pub velocity_scale: f32,
+}
+
+impl Default for DebugRenderSettings {
+    fn default() -> Self {
+        Self {
+            contacts: false,
+            contact_impulse_scale: 0.1,
+            joints: false,
+            joint_error_tolerance: 0.1,
+            body_states: false,
+            aabbs: false,
+            velocities: false,
+            centers_of_mass: false,
+            velocity_scale: 0.1,
+        }
+    }
+}
+
+/// A renderer for the debug geometry of the physics world.
+///
+/// Positions and lengths are in Rapier units, each backend maps them to its own space.
+/// The `render` systems use the macroquad backend, and `SvgBackend` draws without a window.
+pub trait DebugDrawBackend {
+    /// Draws a line segment.
+    fn draw_line(&mut self, a: &Point<f32>, b: &Point<f32>, color: DebugColor);
+
+    /// Draws a filled convex polygon. In 3D, the points lie on a plane.
+    fn draw_polygon(&mut self, points: &[Point<f32>], color: DebugColor);
+
+    /// Draws a filled disk in 2D, or a ball in 3D.
+    fn draw_circle(&mut self, center: &Point<f32>, radius: f32, color: DebugColor);
+
+    /// Draws a marker whose size does not depend on the scale of the drawing.
+    fn draw_point(&mut self, point: &Point<f32>, color: DebugColor);
+
+    /// Draws a text label.
+    fn draw_text(&mut self, position: &Point<f32>, text: &str, color: DebugColor);
+}
+
+/// Number of segments used to draw a half-circle, or the slices of a 3D round shape.
+pub(crate) const SUBDIVISIONS: u32 = 16;
+
+/// Half-length, in Rapier units, of the boundary drawn for a `HalfSpace`.
+const HALF_SPACE_EXTENT: f32 = 1000.0;
+
+/// Length, in Rapier units, of the axes and the radius of the arcs drawn for the overlays.
+const DEBUG_AXIS_LENGTH: f32 = 0.5;
+
+/// Draws the colliders of the World, and the overlays enabled by its `DebugRenderSettings`,
+/// with any backend.
+///
+/// This is what the `render` systems draw, in a single call. The colliders and joints are
+/// drawn at their `InterpolatedTransform` like `render_colliders` and `render_joints` do, but
+/// the 3D meshes are computed again every call instead of being kept in a `ColliderMeshCache`.
+/// Without `DebugRenderSettings`, only the colliders are drawn.
+pub fn draw_physics(world: &World, backend: &mut impl DebugDrawBackend) -> Result<(), error::Run> {
+    world.run(
+        |configuration: UniqueView<RapierConfiguration>,
+         integration_parameters: UniqueView<IntegrationParameters>,
+         bodies: UniqueView<RigidBodySet>,
+         colliders: UniqueView<ColliderSet>,
+         narrow_phase: UniqueView<NarrowPhase>,
+         joints: UniqueView<JointSet>,
+         entity_maps: UniqueView<EntityMaps>,
+         (collider_handles, render_colors, interpolated_transforms): (
+            View<ColliderHandleComponent>,
+            View<RapierRenderColor>,
+            View<InterpolatedTransform>,
+        ),
+         settings: Option<UniqueView<DebugRenderSettings>>| {
+            let settings = settings.map(|settings| *settings).unwrap_or_default();
+            draw_colliders(
+                backend,
+                &bodies,
+                &colliders,
+                &entity_maps,
+                &collider_handles,
+                &render_colors,
+                &interpolated_transforms,
+                &settings,
+                configuration.scale,
+            );
+            draw_overlays(backend, &settings, &bodies, &colliders);
+            draw_contacts(
+                backend,
+                &settings,
+                &narrow_phase,
+                &colliders,
+                integration_parameters.prediction_distance,
+            );
+            draw_joints(
+                backend,
+                &settings,
+                &bodies,
+                &joints,
+                &entity_maps,
+                &interpolated_transforms,
+                configuration.scale,
+            );
+        },
+    )
+}
+
+/// Draws a shape at the given position. Compound shapes are drawn by recursing into
+/// their sub-shapes, and user-defined shapes are not drawn.
+#[cfg(feature = "dim2")]
+pub fn draw_shape(
+    backend: &mut impl DebugDrawBackend,
+    shape: &dyn Shape,
+    pos: &Isometry<f32>,
+    color: DebugColor,
+) {
+    let transformed =
+        |points: &[Point<f32>]| -> Vec<_> { points.iter().map(|p| pos * p).collect() };
+
+    match shape.shape_type() {
+        ShapeType::Ball => {
+            let b = shape.as_ball().unwrap();
+            backend.draw_circle(&(pos * Point::origin()), b.radius, color);
+        }
+        ShapeType::Cuboid => {
+            let c = shape.as_cuboid().unwrap();
+            backend.draw_polygon(&transformed(&c.to_polyline()), color);
+        }
+        ShapeType::Capsule => {
+            let c = shape.as_capsule().unwrap();
+            backend.draw_polygon(&transformed(&c.to_polyline(SUBDIVISIONS)), color);
+        }
+        ShapeType::Segment => {
+            let s = shape.as_shape::<Segment>().unwrap();
+            backend.draw_line(&(pos * s.a), &(pos * s.b), color);
+        }
+        ShapeType::Triangle => {
+            let t = shape.as_triangle().unwrap();
+            backend.draw_polygon(&transformed(t.vertices()), color);
+        }
+        ShapeType::TriMesh => {
+            let t = shape.as_trimesh().unwrap();
+            for triangle in t.triangles() {
+                backend.draw_polygon(&transformed(triangle.vertices()), color);
+            }
+        }
+        ShapeType::Polyline => {
+            let p = shape.as_polyline().unwrap();
+            for segment in p.segments() {
+                backend.draw_line(&(pos * segment.a), &(pos * segment.b), color);
+            }
+        }
+        ShapeType::HalfSpace => {
+            let h = shape.as_shape::<HalfSpace>().unwrap();
+            let tangent = Vector::new(-h.normal.y, h.normal.x) * HALF_SPACE_EXTENT;
+            backend.draw_line(
+                &(pos * Point::from(-tangent)),
+                &(pos * Point::from(tangent)),
+                color,
+            );
+        }
+        ShapeType::HeightField => {
+            let h = shape.as_heightfield().unwrap();
+            for segment in h.segments() {
+                backend.draw_line(&(pos * segment.a), &(pos * segment.b), color);
+            }
+        }
+        ShapeType::ConvexPolygon => {
+            let p = shape.as_convex_polygon().unwrap();
+            backend.draw_polygon(&transformed(p.points()), color);
+        }
+        ShapeType::RoundCuboid => {
+            let c = shape.as_round_cuboid().unwrap();
+            let points = transformed(&c.base_shape.to_polyline());
+            draw_round_polygon(backend, &points, c.border_radius, color);
+        }
+        ShapeType::RoundTriangle => {
+            let t = shape.as_round_triangle().unwrap();
+            let points = transformed(t.base_shape.vertices());
+            draw_round_polygon(backend, &points, t.border_radius, color);
+        }
+        ShapeType::RoundConvexPolygon => {
+            let p = shape.as_round_convex_polygon().unwrap();
+            let points = transformed(p.base_shape.points());
+            draw_round_polygon(backend, &points, p.border_radius, color);
+        }
+        ShapeType::Compound => {
+            let c = shape.as_compound().unwrap();
+            for (sub_pos, sub_shape) in c.shapes() {
+                draw_shape(backend, &**sub_shape, &(pos * sub_pos), color);
+            }
+        }
+        // User-defined shapes cannot be drawn.
+        ShapeType::Custom => {}
+    };
+}
+
+/// Draws a convex polygon dilated by a disk: the polygon, a disk on each vertex
+/// and a rectangle along each edge.
+#[cfg(feature = "dim2")]
+fn draw_round_polygon(
+    backend: &mut impl DebugDrawBackend,
+    points: &[Point<f32>],
+    radius: f32,
+    color: DebugColor,
+) {
+    backend.draw_polygon(points, color);
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        backend.draw_circle(a, radius, color);
+        if let Some(edge) = (b - a).try_normalize(f32::EPSILON) {
+            let offset = Vector::new(-edge.y, edge.x) * radius;
+            backend.draw_polygon(&[a - offset, b - offset, b + offset, a + offset], color);
+        }
+    }
+}
+
+/// Draws a shape at the given position. Compound shapes are drawn by recursing into
+/// their sub-shapes, and user-defined shapes are not drawn.
+///
+/// Balls are drawn as balls, segments and polylines as lines, and the other shapes as
+/// the triangles of their mesh.
+#[cfg(feature = "dim3")]
+pub fn draw_shape(
+    backend: &mut impl DebugDrawBackend,
+    shape: &dyn Shape,
+    pos: &Isometry<f32>,
+    color: DebugColor,
+) {
+    match shape.shape_type() {
+        ShapeType::Ball => {
+            let b = shape.as_ball().unwrap();
+            backend.draw_circle(&(pos * Point::origin()), b.radius, color);
+        }
+        ShapeType::Segment => {
+            let s = shape.as_shape::<Segment>().unwrap();
+            backend.draw_line(&(pos * s.a), &(pos * s.b), color);
+        }
+        ShapeType::Polyline => {
+            let p = shape.as_polyline().unwrap();
+            for segment in p.segments() {
+                backend.draw_line(&(pos * segment.a), &(pos * segment.b), color);
+            }
+        }
+        ShapeType::Compound => {
+            let c = shape.as_compound().unwrap();
+            for (sub_pos, sub_shape) in c.shapes() {
+                draw_shape(backend, &**sub_shape, &(pos * sub_pos), color);
+            }
+        }
+        _ => {
+            let (vertices, indices) = shape_mesh(shape);
+            for [a, b, c] in indices {
+                backend.draw_polygon(
+                    &[
+                        pos * vertices[a as usize],
+                        pos * vertices[b as usize],
+                        pos * vertices[c as usize],
+                    ],
+                    color,
+                );
+            }
+        }
+    }
+}
+
+/// Computes the triangle mesh drawn for a shape.
+#[cfg(feature = "dim3")]
+pub(crate) fn shape_mesh(shape: &dyn Shape) -> (Vec<Point<f32>>, Vec<[u32; 3]>) {
+    match shape.shape_type() {
+        ShapeType::Ball => {
+            let b = shape.as_ball().unwrap();
+            b.to_trimesh(SUBDIVISIONS, SUBDIVISIONS / 2)
+        }
+        ShapeType::Cuboid => {
+            let c = shape.as_cuboid().unwrap();
+            c.to_trimesh()
+        }
+        ShapeType::Capsule => {
+            let c = shape.as_capsule().unwrap();
+            c.to_trimesh(SUBDIVISIONS, SUBDIVISIONS / 2)
+        }
+        ShapeType::Triangle => {
+            let t = shape.as_triangle().unwrap();
+            (t.vertices().to_vec(), vec![[0, 1, 2]])
+        }
+        ShapeType::TriMesh => {
+            let t = shape.as_trimesh().unwrap();
+            (t.vertices().to_vec(), t.indices().to_vec())
+        }
+        ShapeType::HalfSpace => {
+            let h = shape.as_shape::<HalfSpace>().unwrap();
+            let axis = if h.normal.x.abs() < 0.5 {
+                Vector::x()
+            } else {
+                Vector::y()
+            };
+            let tangent1 = h.normal.cross(&axis).normalize() * HALF_SPACE_EXTENT;
+            let tangent2 = h.normal.cross(&tangent1);
+            let vertices = vec![
+                Point::from(tangent1 + tangent2),
+                Point::from(tangent2 - tangent1),
+                Point::from(-tangent1 - tangent2),
+                Point::from(tangent1 - tangent2),
+            ];
+            (vertices, vec![[0, 1, 2], [0, 2, 3]])
+        }
+        ShapeType::HeightField => {
+            let h = shape.as_heightfield().unwrap();
+            h.to_trimesh()
+        }
+        ShapeType::ConvexPolyhedron => {
+            let p = shape.as_convex_polyhedron().unwrap();
+            p.to_trimesh()
+        }
+        ShapeType::Cylinder => {
+            let c = shape.as_cylinder().unwrap();
+            c.to_trimesh(SUBDIVISIONS)
+        }
+        ShapeType::Cone => {
+            let c = shape.as_cone().unwrap();
+            c.to_trimesh(SUBDIVISIONS)
+        }
+        ShapeType::RoundCuboid => {
+            let c = shape.as_round_cuboid().unwrap();
+            dilated(c.base_shape.to_trimesh(), c.border_radius)
+        }
+        ShapeType::RoundTriangle => {
+            let t = shape.as_round_triangle().unwrap();
+            // The triangle is drawn on both sides of its plane.
+            let offset = t.base_shape.normal().map_or(Vector::zeros(), |normal| {
+                normal.into_inner() * t.border_radius
+            });
+            let vertices = t
+                .base_shape
+                .vertices()
+                .iter()
+                .flat_map(|vertex| vec![vertex + offset, vertex - offset])
+                .collect();
+            (vertices, vec![[0, 2, 4], [1, 3, 5]])
+        }
+        ShapeType::RoundCylinder => {
+            let c = shape.as_round_cylinder().unwrap();
+            dilated(c.base_shape.to_trimesh(SUBDIVISIONS), c.border_radius)
+        }
+        ShapeType::RoundCone => {
+            let c = shape.as_round_cone().unwrap();
+            dilated(c.base_shape.to_trimesh(SUBDIVISIONS), c.border_radius)
+        }
+        ShapeType::RoundConvexPolyhedron => {
+            let p = shape.as_round_convex_polyhedron().unwrap();
+            dilated(p.base_shape.to_trimesh(), p.border_radius)
+        }
+        // Segments and polylines are drawn as lines, compound shapes as their sub-shapes,
+        // and user-defined shapes cannot be drawn.
+        ShapeType::Segment | ShapeType::Polyline | ShapeType::Compound | ShapeType::Custom => {
+            (Vec::new(), Vec::new())
+        }
+    }
+}
+
+/// Approximates a mesh dilated by a ball, by moving each vertex along its normal.
+#[cfg(feature = "dim3")]
+fn dilated(
+    (mut vertices, indices): (Vec<Point<f32>>, Vec<[u32; 3]>),
+    radius: f32,
+) -> (Vec<Point<f32>>, Vec<[u32; 3]>) {
+    let mut normals = vec![Vector::zeros(); vertices.len()];
+    for [a, b, c] in &indices {
+        let (a, b, c) = (*a as usize, *b as usize, *c as usize);
+        let normal = (vertices[b] - vertices[a]).cross(&(vertices[c] - vertices[a]));
+        if let Some(normal) = normal.try_normalize(f32::EPSILON) {
+            normals[a] += normal;
+            normals[b] += normal;
+            normals[c] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        *vertex += normal.try_normalize(f32::EPSILON).unwrap_or_default() * radius;
+    }
+    (vertices, indices)
+}
+
+/// Calls `f` with each collider of a `ColliderHandleComponent`, its position and its color.
+///
+/// Colliders attached to a rigid-body with an `InterpolatedTransform` are at their
+/// interpolated position. Colliders without a `RapierRenderColor` are colored by
+/// rigid-body, or by the state of their rigid-body when `body_states` is set.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn visit_colliders(
+    bodies: &RigidBodySet,
+    colliders: &ColliderSet,
+    entity_maps: &EntityMaps,
+    collider_handles: &View<ColliderHandleComponent>,
+    render_colors: &View<RapierRenderColor>,
+    interpolated_transforms: &View<InterpolatedTransform>,
+    body_states: bool,
+    scale: f32,
+    mut f: impl FnMut(ColliderHandle, &Collider, &Isometry<f32>, DebugColor),
+) {
+    let mut icolor = 0;
+    let mut body_colors = HashMap::new();
+
+    for (entity, collider_handle) in collider_handles.iter().with_id() {
+        if let Some(collider) = colliders.get(collider_handle.handle()) {
+            if let Some(body) = bodies.get(collider.parent()) {
+                let default_color = if body_states {
+                    body_state_color(body)
+                } else if body.is_static() {
+                    GROUND_COLOR
+                } else {
+                    *body_colors.entry(collider.parent()).or_insert_with(|| {
+                        icolor += 1;
+                        PALLETE[icolor % PALLETE.len()]
+                    })
+                };
+
+                let render_color = render_colors.get(entity).ok();
+
+                let color = render_color
+                    .map(|c| DebugColor::new(c.0, c.1, c.2, 1.0))
+                    .unwrap_or(default_color);
+
+                let position = entity_maps
+                    .body_entity(collider.parent())
+                    .and_then(|body_entity| interpolated_transforms.get(body_entity).ok())
+                    .map(|interpolated| {
+                        interpolated.to_physics(scale) * collider.position_wrt_parent()
+                    })
+                    .unwrap_or(*collider.position());
+
+                f(collider_handle.handle(), collider, &position, color);
+            }
+        }
+    }
+}
+
+/// Draws the colliders of the `ColliderHandleComponent`s, see `visit_colliders`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_colliders(
+    backend: &mut impl DebugDrawBackend,
+    bodies: &RigidBodySet,
+    colliders: &ColliderSet,
+    entity_maps: &EntityMaps,
+    collider_handles: &View<ColliderHandleComponent>,
+    render_colors: &View<RapierRenderColor>,
+    interpolated_transforms: &View<InterpolatedTransform>,
+    settings: &DebugRenderSettings,
+    scale: f32,
+) {
+    visit_colliders(
+        bodies,
+        colliders,
+        entity_maps,
+        collider_handles,
+        render_colors,
+        interpolated_transforms,
+        settings.body_states,
+        scale,
+        |_, collider, position, color| draw_shape(backend, collider.shape(), position, color),
+    );
+}
+
+/// The color of the colliders of a rigid-body, given its state.
+pub(crate) fn body_state_color(body: &RigidBody) -> DebugColor {
+    if body.is_static() {
+        GROUND_COLOR
+    } else if body.is_kinematic() {
+        KINEMATIC_COLOR
+    } else if body.is_sleeping() {
+        SLEEPING_COLOR
+    } else {
+        AWAKE_COLOR
+    }
+}
+
+/// Draws the AABBs of the colliders, and the velocities and centers of mass of the
+/// rigid-bodies, as enabled by the `DebugRenderSettings`.
+///
+/// The velocities are drawn from the center of mass, as the motion of the rigid-body
+/// during `DebugRenderSettings::velocity_scale` seconds. Static rigid-bodies are skipped.
+pub(crate) fn draw_overlays(
+    backend: &mut impl DebugDrawBackend,
+    settings: &DebugRenderSettings,
+    bodies: &RigidBodySet,
+    colliders: &ColliderSet,
+) {
+    if settings.aabbs {
+        for (_, collider) in colliders.iter() {
+            draw_aabb(backend, &collider.compute_aabb(), AABB_COLOR);
+        }
+    }
+
+    if !settings.velocities && !settings.centers_of_mass {
+        return;
+    }
+    for (_, body) in bodies.iter().filter(|(_, body)| !body.is_static()) {
+        let center_of_mass = body.position() * body.mass_properties().local_com;
+        let color = body_state_color(body);
+        if settings.velocities && !body.is_sleeping() {
+            let motion = body.linvel() * settings.velocity_scale;
+            backend.draw_line(&center_of_mass, &(center_of_mass + motion), color);
+
+            #[cfg(feature = "dim2")]
+            draw_arc(
+                backend,
+                &center_of_mass,
+                &(body.position().rotation * Vector::x() * DEBUG_AXIS_LENGTH),
+                body.angvel() * settings.velocity_scale,
+                ANGULAR_VELOCITY_COLOR,
+            );
+            #[cfg(feature = "dim3")]
+            backend.draw_line(
+                &center_of_mass,
+                &(center_of_mass + body.angvel() * settings.velocity_scale),
+                ANGULAR_VELOCITY_COLOR,
+            );
+        }
+        if settings.centers_of_mass {
+            backend.draw_point(&center_of_mass, color);
+        }
+    }
+}
+
+/// Draws the edges of an AABB.
+fn draw_aabb(backend: &mut impl DebugDrawBackend, aabb: &AABB, color: DebugColor) {
+    let corner = |i: usize| {
+        Point::from(Vector::from_fn(|k, _| {
+            if i & (1 << k) != 0 {
+                aabb.maxs[k]
+            } else {
+                aabb.mins[k]
+            }
+        }))
+    };
+    for i in 0..1 << DIM {
+        for k in 0..DIM {
+            if i & (1 << k) == 0 {
+                backend.draw_line(&corner(i), &corner(i | (1 << k)), color);
+            }
+        }
+    }
+}
+
+/// Draws the arc described by rotating `from` around `center`, by `angle` radians.
+#[cfg(feature = "dim2")]
+fn draw_arc(
+    backend: &mut impl DebugDrawBackend,
+    center: &Point<f32>,
+    from: &Vector<f32>,
+    angle: f32,
+    color: DebugColor,
+) {
+    let arc_point = |t: f32| center + Rotation::new(angle * t) * from;
+    for i in 0..SUBDIVISIONS {
+        let t = i as f32 / SUBDIVISIONS as f32;
+        let next_t = (i + 1) as f32 / SUBDIVISIONS as f32;
+        backend.draw_line(&arc_point(t), &arc_point(next_t), color);
+    }
+}
+
+/// Draws the arc described by rotating `from` around `axis` through `center`, by `angle` radians.
+#[cfg(feature = "dim3")]
+fn draw_arc(
+    backend: &mut impl DebugDrawBackend,
+    center: &Point<f32>,
+    axis: &Unit<Vector<f32>>,
+    from: &Vector<f32>,
+    angle: f32,
+    color: DebugColor,
+) {
+    let arc_point = |t: f32| center + Rotation::from_axis_angle(axis, angle * t) * from;
+    for i in 0..SUBDIVISIONS {
+        let t = i as f32 / SUBDIVISIONS as f32;
+        let next_t = (i + 1) as f32 / SUBDIVISIONS as f32;
+        backend.draw_line(&arc_point(t), &arc_point(next_t), color);
+    }
+}
+
+/// Draws the contacts of the narrow-phase, when enabled by the `DebugRenderSettings`.
+///
+/// Only the contacts seen by the constraints solver are drawn. Each contact point is drawn
+/// with the contact normal, whose length is proportional to the impulse applied by the
+/// last step, and the penetration depth along the normal.
+pub(crate) fn draw_contacts(
+    backend: &mut impl DebugDrawBackend,
+    settings: &DebugRenderSettings,
+    narrow_phase: &NarrowPhase,
+    colliders: &ColliderSet,
+    prediction_distance: f32,
+) {
+    if !settings.contacts {
+        return;
+    }
+
+    for contact in active_contacts(narrow_phase, colliders, prediction_distance) {
+        let depth = contact.normal * contact.depth / 2.0;
+        let impulse = contact.normal * contact.impulse * settings.contact_impulse_scale;
+        backend.draw_line(
+            &(contact.point - depth),
+            &(contact.point + depth),
+            CONTACT_COLOR,
+        );
+        backend.draw_line(&contact.point, &(contact.point + impulse), NORMAL_COLOR);
+        backend.draw_point(&contact.point, CONTACT_COLOR);
+    }
+}
+
+/// A contact point of the narrow-phase, in Rapier units.
+struct DebugContact {
+    /// The point halfway between the two colliders.
+    point: Point<f32>,
+    /// The contact normal, pointing from the first collider to the second one.
+    normal: Vector<f32>,
+    /// The impulse applied along the normal by the last step.
+    impulse: f32,
+    /// The penetration depth, zero if the colliders are not penetrating.
+    depth: f32,
+}
+
+/// Collects the contacts of the active contact pairs seen by the constraints solver.
+///
+/// The solver writes its impulses to the contacts of the manifolds, the solver contacts
+/// only hold the impulses of the previous step.
+fn active_contacts(
+    narrow_phase: &NarrowPhase,
+    colliders: &ColliderSet,
+    prediction_distance: f32,
+) -> Vec<DebugContact> {
+    let mut contacts = Vec::new();
+    for pair in narrow_phase.contact_pairs() {
+        if !pair.has_any_active_contact {
+            continue;
+        }
+        let collider1 = match colliders.get(pair.pair.collider1) {
+            Some(collider) => collider,
+            None => continue,
+        };
+        for manifold in &pair.manifolds {
+            if manifold.data.solver_contacts.is_empty() {
+                continue;
+            }
+            let position1 = match manifold.subshape_pos1 {
+                Some(subshape_pos) => collider1.position() * subshape_pos,
+                None => *collider1.position(),
+            };
+            let normal = manifold.data.normal;
+            for point in &manifold.points {
+                if point.dist < prediction_distance {
+                    contacts.push(DebugContact {
+                        point: position1 * point.local_p1 + normal * point.dist / 2.0,
+                        normal,
+                        impulse: point.data.impulse,
+                        depth: (-point.dist).max(0.0),
+                    });
+                }
+            }
+        }
+    }
+    contacts
+}
+
+/// Draws the joints of the `JointSet`, when enabled by the `DebugRenderSettings`.
+///
+/// Each joint is drawn with lines from its rigid-bodies to their anchor, and between the
+/// anchors. Fixed joints show the axes of their frame, prismatic joints their axis and
+/// limits and, in 3D, revolute joints their axis and the angle between their bases, as
+/// Rapier joints have no angular limits. Joints whose anchors drifted apart, or attached
+/// to a removed rigid-body, are highlighted.
+pub(crate) fn draw_joints(
+    backend: &mut impl DebugDrawBackend,
+    settings: &DebugRenderSettings,
+    bodies: &RigidBodySet,
+    joints: &JointSet,
+    entity_maps: &EntityMaps,
+    interpolated_transforms: &View<InterpolatedTransform>,
+    scale: f32,
+) {
+    if !settings.joints {
+        return;
+    }
+
+    let body_position = |handle| {
+        let body = bodies.get(handle)?;
+        Some(
+            entity_maps
+                .body_entity(handle)
+                .and_then(|body_entity| interpolated_transforms.get(body_entity).ok())
+                .map(|interpolated| interpolated.to_physics(scale))
+                .unwrap_or(*body.position()),
+        )
+    };
+
+    for (_, joint) in joints.iter() {
+        match (body_position(joint.body1), body_position(joint.body2)) {
+            (Some(position1), Some(position2)) => {
+                draw_joint(backend, &joint.params, &position1, &position2, settings)
+            }
+            (Some(position), None) | (None, Some(position)) => backend.draw_point(
+                &Point::from(position.translation.vector),
+                BROKEN_JOINT_COLOR,
+            ),
+            (None, None) => {}
+        }
+    }
+}
+
+fn draw_joint(
+    backend: &mut impl DebugDrawBackend,
+    params: &JointParams,
+    position1: &Isometry<f32>,
+    position2: &Isometry<f32>,
+    settings: &DebugRenderSettings,
+) {
+    let (frame1, frame2) = joint_frames(params, position1, position2);
+    let anchor1 = Point::from(frame1.translation.vector);
+    let anchor2 = Point::from(frame2.translation.vector);
+    let color = if joint_error(params, &frame1, &frame2) > settings.joint_error_tolerance {
+        BROKEN_JOINT_COLOR
+    } else {
+        JOINT_COLOR
+    };
+
+    backend.draw_line(&Point::from(position1.translation.vector), &anchor1, color);
+    backend.draw_line(&Point::from(position2.translation.vector), &anchor2, color);
+    backend.draw_line(&anchor1, &anchor2, color);
+
+    match params {
+        JointParams::BallJoint(_) => {}
+        JointParams::FixedJoint(_) => {
+            for (i, axis_color) in AXIS_COLORS.iter().enumerate().take(DIM) {
+                let axis = frame1.rotation * Vector::ith(i, DEBUG_AXIS_LENGTH);
+                backend.draw_line(&anchor1, &(anchor1 + axis), *axis_color);
+            }
+        }
+        JointParams::PrismaticJoint(joint) => {
+            let axis = frame1.rotation * joint.local_axis1().into_inner();
+            if joint.limits_enabled {
+                let min = anchor1 + axis * joint.limits[0];
+                let max = anchor1 + axis * joint.limits[1];
+                backend.draw_line(&min, &max, LIMIT_COLOR);
+                backend.draw_point(&min, LIMIT_COLOR);
+                backend.draw_point(&max, LIMIT_COLOR);
+            } else {
+                let axis = axis * DEBUG_AXIS_LENGTH;
+                backend.draw_line(&(anchor1 - axis), &(anchor1 + axis), LIMIT_COLOR);
+            }
+        }
+        #[cfg(feature = "dim3")]
+        JointParams::RevoluteJoint(joint) => {
+            let axis = frame1.rotation * joint.local_axis1;
+            let basis1 = frame1.rotation * joint.basis1[0];
+            let basis2 = frame2.rotation * joint.basis2[0];
+            let angle = axis.dot(&basis1.cross(&basis2)).atan2(basis1.dot(&basis2));
+            backend.draw_line(
+                &anchor1,
+                &(anchor1 + axis.into_inner() * DEBUG_AXIS_LENGTH),
+                LIMIT_COLOR,
+            );
+            draw_arc(
+                backend,
+                &anchor1,
+                &axis,
+                &(basis1 * DEBUG_AXIS_LENGTH),
+                angle,
+                LIMIT_COLOR,
+            );
+        }
+    }
+}
+
+/// The world-space frames of the anchors of a joint, given the positions of its rigid-bodies.
+///
+/// The frames of joints anchored on a point have the orientation of their rigid-body.
+fn joint_frames(
+    params: &JointParams,
+    position1: &Isometry<f32>,
+    position2: &Isometry<f32>,
+) -> (Isometry<f32>, Isometry<f32>) {
+    let anchored =
+        |position: &Isometry<f32>, anchor: &Point<f32>| position * Translation::from(anchor.coords);
+    match params {
+        JointParams::BallJoint(joint) => (
+            anchored(position1, &joint.local_anchor1),
+            anchored(position2, &joint.local_anchor2),
+        ),
+        JointParams::FixedJoint(joint) => (
+            position1 * joint.local_anchor1,
+            position2 * joint.local_anchor2,
+        ),
+        JointParams::PrismaticJoint(joint) => (
+            anchored(position1, &joint.local_anchor1),
+            anchored(position2, &joint.local_anchor2),
+        ),
+        #[cfg(feature = "dim3")]
+        JointParams::RevoluteJoint(joint) => (
+            anchored(position1, &joint.local_anchor1),
+            anchored(position2, &joint.local_anchor2),
+        ),
+    }
+}
+
+/// How far the anchors of a joint are from satisfying its positional constraint.
+///
+/// The anchors of a prismatic joint can move along its axis, within its limits.
+fn joint_error(params: &JointParams, frame1: &Isometry<f32>, frame2: &Isometry<f32>) -> f32 {
+    let offset = frame2.translation.vector - frame1.translation.vector;
+    match params {
+        JointParams::PrismaticJoint(joint) => {
+            let axis = frame1.rotation * joint.local_axis1();
+            let along = axis.dot(&offset);
+            let off_axis = (offset - axis.into_inner() * along).norm();
+            let beyond_limits = if joint.limits_enabled {
+                (joint.limits[0] - along)
+                    .max(along - joint.limits[1])
+                    .max(0.0)
+            } else {
+                0.0
+            };
+            off_axis.hypot(beyond_limits)
+        }
+        _ => offset.norm(),
+    }
+}
+
+#[test]
+fn test_active_contacts() {
+    use crate::physics::{create_body_and_collider_system, step_world_system, PhysicsSetup};
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::ColliderBuilder;
+
+    let mut world = World::new();
+    PhysicsSetup::new().gravity(Vector::zeros()).install(&world);
+
+    // two overlapping balls, one of them moving into the other
+    let moving =
+        RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::x() * 0.8).into());
+    #[cfg(feature = "dim2")]
+    let moving = moving.linvel(-1.0, 0.0);
+    #[cfg(feature = "dim3")]
+    let moving = moving.linvel(-1.0, 0.0, 0.0);
+    world.add_entity((RigidBodyBuilder::new_dynamic(), ColliderBuilder::ball(0.5)));
+    world.add_entity((moving, ColliderBuilder::ball(0.5)));
+    // and a ball far from them
+    world.add_entity((
+        RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::x() * 10.0).into()),
+        ColliderBuilder::ball(0.5),
+    ));
+    world.run(create_body_and_collider_system).unwrap();
+    world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+
+    world
+        .run(
+            |colliders: UniqueView<ColliderSet>,
+             narrow_phase: UniqueView<NarrowPhase>,
+             integration_parameters: UniqueView<IntegrationParameters>| {
+                let contacts = active_contacts(
+                    &narrow_phase,
+                    &colliders,
+                    integration_parameters.prediction_distance,
+                );
+                assert_eq!(contacts.len(), 1);
+                let contact = &contacts[0];
+                assert!((contact.normal.x.abs() - 1.0).abs() < 1.0e-3);
+                assert!((contact.point.x - 0.4).abs() < 0.1);
+                assert!(contact.depth > 0.0 && contact.depth < 0.2);
+                assert!(contact.impulse > 0.0);
+            },
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_joint_error() {
+    use rapier::dynamics::{BallJoint, PrismaticJoint};
+
+    #[cfg(feature = "dim2")]
+    let prismatic_joint = |axis, _tangent: Vector<f32>| {
+        PrismaticJoint::new(Point::origin(), axis, Point::origin(), axis)
+    };
+    #[cfg(feature = "dim3")]
+    let prismatic_joint = |axis, tangent| {
+        PrismaticJoint::new(
+            Point::origin(),
+            axis,
+            tangent,
+            Point::origin(),
+            axis,
+            tangent,
+        )
+    };
+
+    let position1 = Isometry::identity();
+    let position2 = Translation::from(Vector::x()).into();
+
+    // a ball joint between the two rigid-bodies, and one with a misplaced anchor
+    let joint = JointParams::from(BallJoint::new(Point::from(Vector::x()), Point::origin()));
+    let (frame1, frame2) = joint_frames(&joint, &position1, &position2);
+    assert!(joint_error(&joint, &frame1, &frame2) < 1.0e-5);
+    let joint = JointParams::from(BallJoint::new(Point::origin(), Point::origin()));
+    let (frame1, frame2) = joint_frames(&joint, &position1, &position2);
+    assert!((joint_error(&joint, &frame1, &frame2) - 1.0).abs() < 1.0e-5);
+
+    // a prismatic joint along x, with the second anchor 1 unit along its axis
+    let mut prismatic = prismatic_joint(Vector::x_axis(), Vector::y());
+    let joint = JointParams::from(prismatic);
+    let (frame1, frame2) = joint_frames(&joint, &position1, &position2);
+    assert!(joint_error(&joint, &frame1, &frame2) < 1.0e-5);
+
+    prismatic.limits_enabled = true;
+    prismatic.limits = [-0.5, 0.5];
+    let joint = JointParams::from(prismatic);
+    assert!((joint_error(&joint, &frame1, &frame2) - 0.5).abs() < 1.0e-5);
+
+    let joint = JointParams::from(prismatic_joint(Vector::y_axis(), Vector::x()));
+    assert!((joint_error(&joint, &frame1, &frame2) - 1.0).abs() < 1.0e-5);
+}
+
+#[test]
+fn test_body_state_color() {
+    use rapier::dynamics::RigidBodyBuilder;
+
+    let mut bodies = RigidBodySet::new();
+    let mut state_color = |builder: RigidBodyBuilder| {
+        let handle = bodies.insert(builder.build());
+        body_state_color(&bodies[handle])
+    };
+    assert_eq!(state_color(RigidBodyBuilder::new_static()), GROUND_COLOR);
+    assert_eq!(
+        state_color(RigidBodyBuilder::new_kinematic()),
+        KINEMATIC_COLOR
+    );
+    assert_eq!(state_color(RigidBodyBuilder::new_dynamic()), AWAKE_COLOR);
+    assert_eq!(
+        state_color(RigidBodyBuilder::new_dynamic().sleeping(true)),
+        SLEEPING_COLOR
+    );
+}
+
+#[test]
+fn test_draw_physics() {
+    use crate::physics::{create_body_and_collider_system, PhysicsSetup};
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::ColliderBuilder;
+
+    let mut world = World::new();
+    PhysicsSetup::new().install(&world);
+
+    // a ball above a static ground
+    #[cfg(feature = "dim2")]
+    let ground = ColliderBuilder::cuboid(5.0, 0.1);
+    #[cfg(feature = "dim3")]
+    let ground = ColliderBuilder::cuboid(5.0, 0.1, 5.0);
+    world.add_entity((RigidBodyBuilder::new_static(), ground));
+    world.add_entity((
+        RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::y()).into()),
+        ColliderBuilder::ball(0.5),
+    ));
+    world.run(create_body_and_collider_system).unwrap();
+
+    let mut backend = SvgBackend::new(10.0);
+    draw_physics(&world, &mut backend).unwrap();
+    let svg = backend.to_svg();
+    assert_eq!(svg.matches("<circle").count(), 1);
+    assert!(svg.contains("<polygon") && svg.contains("fill=\"#f3d9b1\""));
+    assert!(!svg.contains("<line"));
+
+    // the edges of the two AABBs
+    world
+        .add_unique(DebugRenderSettings {
+            aabbs: true,
+            ..Default::default()
+        })
+        .unwrap();
+    backend.clear();
+    draw_physics(&world, &mut backend).unwrap();
+    assert_eq!(
+        backend.to_svg().matches("<line").count(),
+        2 * DIM * (1 << (DIM - 1))
+    );
+}
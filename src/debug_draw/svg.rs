@@ -0,0 +1,292 @@
+use super::{DebugColor, DebugDrawBackend};
+#[cfg(feature = "dim3")]
+use rapier::math::Isometry;
+use rapier::math::Point;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Width, in SVG units, of the lines.
+const LINE_WIDTH: f32 = 2.0;
+
+/// Radius, in SVG units, of the point markers.
+const POINT_RADIUS: f32 = 3.0;
+
+/// Font size, in SVG units, of the text labels.
+const FONT_SIZE: f32 = 12.0;
+
+/// Space, in SVG units, left around the drawing when the view box is fitted to it.
+const MARGIN: f32 = 10.0;
+
+/// A `DebugDrawBackend` drawing to an SVG document, without any window or GPU.
+///
+/// One Rapier unit is `scale` SVG units, and the y axis points up like in Rapier.
+/// In 3D, the drawing is an orthographic projection along the z axis of the view.
+/// Unless a view box is given, the document is fitted to what was drawn.
+pub struct SvgBackend {
+    scale: f32,
+    view_box: Option<[f32; 4]>,
+    #[cfg(feature = "dim3")]
+    view: Isometry<f32>,
+    elements: String,
+    mins: [f32; 2],
+    maxs: [f32; 2],
+}
+
+impl SvgBackend {
+    /// Creates an empty drawing, `scale` SVG units per Rapier unit.
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale,
+            view_box: None,
+            #[cfg(feature = "dim3")]
+            view: Isometry::identity(),
+            elements: String::new(),
+            mins: [f32::MAX; 2],
+            maxs: [f32::MIN; 2],
+        }
+    }
+
+    /// Uses a fixed view box, in SVG units: its top left corner, width and height.
+    ///
+    /// A point `p` of Rapier is at `(p.x * scale, -p.y * scale)` in the document.
+    pub fn with_view_box(mut self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.view_box = Some([x, y, width, height]);
+        self
+    }
+
+    /// Draws the points transformed by `view`, seen along its z axis.
+    #[cfg(feature = "dim3")]
+    pub fn with_view(mut self, view: Isometry<f32>) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Whether nothing was drawn since the creation of the drawing or the last `clear`.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Removes everything drawn.
+    pub fn clear(&mut self) {
+        self.elements.clear();
+        self.mins = [f32::MAX; 2];
+        self.maxs = [f32::MIN; 2];
+    }
+
+    /// The SVG document of the drawing.
+    pub fn to_svg(&self) -> String {
+        let [x, y, width, height] = self.view_box.unwrap_or_else(|| {
+            if self.is_empty() {
+                [0.0, 0.0, 0.0, 0.0]
+            } else {
+                [
+                    self.mins[0] - MARGIN,
+                    self.mins[1] - MARGIN,
+                    self.maxs[0] - self.mins[0] + MARGIN * 2.0,
+                    self.maxs[1] - self.mins[1] + MARGIN * 2.0,
+                ]
+            }
+        });
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n{}</svg>\n",
+            number(x),
+            number(y),
+            number(width),
+            number(height),
+            self.elements
+        )
+    }
+
+    /// Writes the SVG document of the drawing to a file.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_svg())
+    }
+
+    /// The position of a point in the document.
+    fn project(&self, point: &Point<f32>) -> [f32; 2] {
+        #[cfg(feature = "dim3")]
+        let point = self.view * point;
+        [point.x * self.scale, -point.y * self.scale]
+    }
+
+    /// Extends the bounds of the drawing to a disk of the document.
+    fn fit(&mut self, [x, y]: [f32; 2], radius: f32) {
+        self.mins = [self.mins[0].min(x - radius), self.mins[1].min(y - radius)];
+        self.maxs = [self.maxs[0].max(x + radius), self.maxs[1].max(y + radius)];
+    }
+
+    fn push_circle(&mut self, center: [f32; 2], radius: f32, color: DebugColor) {
+        self.fit(center, radius);
+        let _ = writeln!(
+            self.elements,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\"{}/>",
+            number(center[0]),
+            number(center[1]),
+            number(radius),
+            paint("fill", color)
+        );
+    }
+}
+
+impl DebugDrawBackend for SvgBackend {
+    fn draw_line(&mut self, a: &Point<f32>, b: &Point<f32>, color: DebugColor) {
+        let (a, b) = (self.project(a), self.project(b));
+        self.fit(a, LINE_WIDTH / 2.0);
+        self.fit(b, LINE_WIDTH / 2.0);
+        let _ = writeln!(
+            self.elements,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-width=\"{}\"{}/>",
+            number(a[0]),
+            number(a[1]),
+            number(b[0]),
+            number(b[1]),
+            number(LINE_WIDTH),
+            paint("stroke", color)
+        );
+    }
+
+    fn draw_polygon(&mut self, points: &[Point<f32>], color: DebugColor) {
+        let mut coordinates = Vec::with_capacity(points.len());
+        for point in points {
+            let point = self.project(point);
+            self.fit(point, 0.0);
+            coordinates.push(format!("{},{}", number(point[0]), number(point[1])));
+        }
+        let _ = writeln!(
+            self.elements,
+            "<polygon points=\"{}\"{}/>",
+            coordinates.join(" "),
+            paint("fill", color)
+        );
+    }
+
+    fn draw_circle(&mut self, center: &Point<f32>, radius: f32, color: DebugColor) {
+        let center = self.project(center);
+        self.push_circle(center, radius * self.scale, color);
+    }
+
+    fn draw_point(&mut self, point: &Point<f32>, color: DebugColor) {
+        let point = self.project(point);
+        self.push_circle(point, POINT_RADIUS, color);
+    }
+
+    fn draw_text(&mut self, position: &Point<f32>, text: &str, color: DebugColor) {
+        let position = self.project(position);
+        self.fit(position, 0.0);
+        let escaped = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let _ = writeln!(
+            self.elements,
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\"{}>{}</text>",
+            number(position[0]),
+            number(position[1]),
+            number(FONT_SIZE),
+            paint("fill", color),
+            escaped
+        );
+    }
+}
+
+/// Writes the drawings of successive frames to numbered SVG files, `frame_00000.svg`,
+/// `frame_00001.svg` and so on, in a directory.
+pub struct SvgFrameWriter {
+    directory: PathBuf,
+    frame: usize,
+}
+
+impl SvgFrameWriter {
+    /// Creates the directory if it does not exist, the first frame written is 0.
+    pub fn new(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            frame: 0,
+        })
+    }
+
+    /// The number of the next frame written.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Writes the drawing to the file of the next frame and clears it, returning the path
+    /// of the file.
+    pub fn write_frame(&mut self, backend: &mut SvgBackend) -> io::Result<PathBuf> {
+        let path = self.directory.join(format!("frame_{:05}.svg", self.frame));
+        backend.write_to(&path)?;
+        backend.clear();
+        self.frame += 1;
+        Ok(path)
+    }
+}
+
+/// Formats a number with at most 3 decimals.
+fn number(value: f32) -> String {
+    let formatted = format!("{:.3}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" {
+        "0".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// The attributes painting an element with a color.
+fn paint(attribute: &str, color: DebugColor) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut attributes = format!(
+        " {}=\"#{:02x}{:02x}{:02x}\"",
+        attribute,
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    );
+    if color.a < 1.0 {
+        let _ = write!(attributes, " {}-opacity=\"{}\"", attribute, number(color.a));
+    }
+    attributes
+}
+
+#[test]
+fn test_svg_backend() {
+    use rapier::math::Vector;
+
+    let mut backend = SvgBackend::new(10.0);
+    assert!(backend.is_empty());
+    assert_eq!(
+        backend.to_svg(),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 0 0\">\n</svg>\n"
+    );
+
+    let red = DebugColor::rgb(0xFF, 0x00, 0x00);
+    backend.draw_line(&Point::origin(), &Point::from(Vector::y()), red);
+    backend.draw_circle(
+        &Point::from(Vector::x()),
+        0.25,
+        DebugColor::new(0.0, 0.0, 1.0, 0.5),
+    );
+    backend.draw_text(&Point::origin(), "a < b", red);
+    assert_eq!(
+        backend.to_svg(),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-11 -21 33.5 33.5\">\n\
+         <line x1=\"0\" y1=\"0\" x2=\"0\" y2=\"-10\" stroke-width=\"2\" stroke=\"#ff0000\"/>\n\
+         <circle cx=\"10\" cy=\"0\" r=\"2.5\" fill=\"#0000ff\" fill-opacity=\"0.5\"/>\n\
+         <text x=\"0\" y=\"0\" font-size=\"12\" fill=\"#ff0000\">a &lt; b</text>\n\
+         </svg>\n"
+    );
+
+    let directory =
+        std::env::temp_dir().join(format!("shipyard_rapier_svg_{}", std::process::id()));
+    let mut writer = SvgFrameWriter::new(&directory).unwrap();
+    let path = writer.write_frame(&mut backend).unwrap();
+    assert_eq!(path, directory.join("frame_00000.svg"));
+    assert!(fs::read_to_string(&path).unwrap().contains("<circle"));
+    assert!(backend.is_empty());
+    assert_eq!(writer.frame(), 1);
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -3,6 +3,7 @@ pub extern crate rapier2d as rapier;
 #[cfg(feature = "dim3")]
 pub extern crate rapier3d as rapier;
 
+pub mod debug_draw;
 pub mod physics;
 
 #[cfg(feature = "render")]
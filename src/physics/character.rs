@@ -0,0 +1,483 @@
+use crate::physics::query::{is_own_collider, layer_groups};
+use crate::physics::{
+    ColliderParent, CollisionLayers, PhysicsQuery, RayHit, RigidBodyHandleComponent,
+};
+
+use rapier::dynamics::RigidBodySet;
+use rapier::geometry::InteractionGroups;
+use rapier::math::{Isometry, Vector};
+use rapier::na::Unit;
+
+use shipyard::{EntitiesView, EntityId, Get, IntoIter, IntoWithId, UniqueViewMut, View, ViewMut};
+
+/// Number of times a movement can slide along the colliders it hits.
+const MAX_SLIDES: usize = 4;
+
+/// A component moving its entity's kinematic rigid-body with collide-and-slide.
+///
+/// `move_characters_system` casts the shape of the entity's collider along
+/// `translation` and, instead of stopping at the first collider hit, slides along it.
+/// Slopes up to `max_slope_climb_angle` are walked on, steeper slopes, walls and
+/// ceilings block the movement. Distances are expressed in world units.
+///
+/// The entity needs a kinematic rigid-body, with colliders on its own entity or attached
+/// through `ColliderParent`s. They are all cast, and only hit the colliders they interact
+/// with according to `groups` and the `CollisionLayers` of the entity. Sensors never block
+/// the character. The controller does not apply gravity, it has to be added to
+/// `translation` like any other movement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharacterController {
+    /// The translation applied at the next `move_characters_system`, reset to zero afterwards.
+    pub translation: Vector<f32>,
+    /// The up direction, separating the ground from the walls and ceilings.
+    pub up: Vector<f32>,
+    /// The gap kept between the character and the colliders.
+    pub offset: f32,
+    /// The maximum angle, in radians, between `up` and the normal of a slope the
+    /// character can climb.
+    pub max_slope_climb_angle: f32,
+    /// The maximum height of the obstacles the character climbs automatically, like stairs.
+    pub step_height: Option<f32>,
+    /// The maximum distance the character is moved down to stay on the ground, like when
+    /// walking down a slope or stairs. The character is not snapped while moving up.
+    pub snap_to_ground: Option<f32>,
+    /// The collision groups of the character shape.
+    pub groups: InteractionGroups,
+}
+
+impl CharacterController {
+    /// Sets the up direction.
+    pub fn with_up(mut self, up: Vector<f32>) -> Self {
+        self.up = up;
+        self
+    }
+
+    /// Sets the gap kept between the character and the colliders.
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the maximum angle, in radians, of the slopes the character can climb.
+    pub fn with_max_slope_climb_angle(mut self, angle: f32) -> Self {
+        self.max_slope_climb_angle = angle;
+        self
+    }
+
+    /// Enables climbing the obstacles up to the given height.
+    pub fn with_step_height(mut self, step_height: f32) -> Self {
+        self.step_height = Some(step_height);
+        self
+    }
+
+    /// Enables snapping to the ground up to the given distance.
+    pub fn with_snap_to_ground(mut self, distance: f32) -> Self {
+        self.snap_to_ground = Some(distance);
+        self
+    }
+
+    /// Sets the collision groups of the character shape.
+    pub fn with_groups(mut self, groups: InteractionGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            translation: Vector::zeros(),
+            up: Vector::y(),
+            offset: 0.01,
+            max_slope_climb_angle: std::f32::consts::FRAC_PI_4,
+            step_height: None,
+            snap_to_ground: None,
+            groups: InteractionGroups::all(),
+        }
+    }
+}
+
+/// A component holding the result of the last movement of a `CharacterController`.
+///
+/// It is added and updated by `move_characters_system`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharacterControllerOutput {
+    /// Whether the character stands on a slope it can climb.
+    pub grounded: bool,
+    /// The entity of the collider the character stands on.
+    pub ground: Option<EntityId>,
+    /// Whether the movement was blocked by a wall, a slope too steep or a ceiling.
+    pub touching_walls: bool,
+    /// The translation actually applied, in world units.
+    pub effective_translation: Vector<f32>,
+}
+
+impl Default for CharacterControllerOutput {
+    fn default() -> Self {
+        Self {
+            grounded: false,
+            ground: None,
+            touching_walls: false,
+            effective_translation: Vector::zeros(),
+        }
+    }
+}
+
+/// System responsible for moving the kinematic rigid-bodies of the `CharacterController`s
+/// and writing the results to their `CharacterControllerOutput`.
+///
+/// The new positions are the kinematic targets of the next step, so this should run after
+/// the systems setting the controllers' translation and before `step_world_system`.
+#[allow(clippy::too_many_arguments)]
+pub fn move_characters_system(
+    entities: EntitiesView,
+    query: PhysicsQuery,
+    mut bodies: UniqueViewMut<RigidBodySet>,
+    body_handles: View<RigidBodyHandleComponent>,
+    (collider_parents, layers): (View<ColliderParent>, View<CollisionLayers>),
+    mut controllers: ViewMut<CharacterController>,
+    mut outputs: ViewMut<CharacterControllerOutput>,
+) {
+    let scale = query.scale();
+
+    for (entity, (body_handle, mut controller)) in
+        (&body_handles, &mut controllers).iter().with_id()
+    {
+        let body = match bodies.get_mut(body_handle.handle()) {
+            Some(body) => body,
+            None => continue,
+        };
+        let colliders: Vec<_> = body
+            .colliders()
+            .iter()
+            .filter_map(|handle| query.colliders().get(*handle))
+            .collect();
+        if colliders.is_empty() {
+            continue;
+        }
+
+        let settings = *controller;
+        let groups = layer_groups(&layers, entity, settings.groups);
+        let mut start = *body.position();
+        start.translation.vector *= scale;
+        let was_grounded = matches!(outputs.get(entity), Ok(output) if output.grounded);
+        let cast = |position: &Isometry<f32>, direction: &Vector<f32>, distance: f32| {
+            let mut closest: Option<RayHit> = None;
+            for collider in &colliders {
+                let mut collider_position = *collider.position_wrt_parent();
+                collider_position.translation.vector *= scale;
+                query.shape_hits(
+                    &(position * collider_position),
+                    direction,
+                    collider.shape(),
+                    distance,
+                    groups,
+                    |hit| {
+                        // Colliders the shape moves away from, like a ground it is
+                        // touching, do not block it.
+                        if hit.normal.dot(direction) < 0.0
+                            && !is_own_collider(&collider_parents, entity, hit.entity)
+                            && closest
+                                .as_ref()
+                                .map_or(true, |closest| hit.toi < closest.toi)
+                        {
+                            closest = Some(hit);
+                        }
+                    },
+                );
+            }
+            closest
+        };
+
+        let output = move_character(&cast, &settings, &start, was_grounded);
+
+        let mut position = *body.position();
+        position.translation.vector += output.effective_translation / scale;
+        body.set_next_kinematic_position(position);
+        controller.translation = Vector::zeros();
+        entities.add_component(entity, &mut outputs, output);
+    }
+}
+
+/// Moves a shape from `start` by the controller's translation, with `cast` returning the
+/// first collider hit by the shape moving along a unit direction.
+fn move_character(
+    cast: &impl Fn(&Isometry<f32>, &Vector<f32>, f32) -> Option<RayHit>,
+    controller: &CharacterController,
+    start: &Isometry<f32>,
+    was_grounded: bool,
+) -> CharacterControllerOutput {
+    let up = controller
+        .up
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vector::y);
+    let min_ground_dot = controller.max_slope_climb_angle.cos();
+    let is_ground = |normal: &Vector<f32>| normal.dot(&up) >= min_ground_dot;
+
+    let mut output = CharacterControllerOutput::default();
+    let mut position = *start;
+    let mut remaining = controller.translation;
+
+    for _ in 0..MAX_SLIDES {
+        let (direction, distance) = match Unit::try_new_and_get(remaining, f32::EPSILON) {
+            Some(direction) => direction,
+            None => break,
+        };
+        let hit = match cast(&position, &direction, distance + controller.offset) {
+            Some(hit) => hit,
+            None => {
+                position.translation.vector += remaining;
+                break;
+            }
+        };
+
+        let travel = (hit.toi - controller.offset).clamp(0.0, distance);
+        position.translation.vector += direction.into_inner() * travel;
+        remaining = direction.into_inner() * (distance - travel);
+
+        if is_ground(&hit.normal) {
+            output.grounded = true;
+            output.ground = Some(hit.entity);
+            remaining -= hit.normal * remaining.dot(&hit.normal);
+            continue;
+        }
+
+        let is_ceiling = hit.normal.dot(&up) < 0.0;
+        if let (Some(step_height), false) = (controller.step_height, is_ceiling) {
+            if was_grounded || output.grounded {
+                if let Some((stepped, left, ground)) = step_up(
+                    cast,
+                    controller,
+                    &up,
+                    &is_ground,
+                    step_height,
+                    &position,
+                    &remaining,
+                ) {
+                    position = stepped;
+                    remaining = left;
+                    output.grounded = true;
+                    output.ground = Some(ground);
+                    continue;
+                }
+            }
+        }
+
+        // Slides along the obstacle, without climbing it.
+        output.touching_walls = true;
+        remaining -= hit.normal * remaining.dot(&hit.normal);
+        let rise = remaining.dot(&up);
+        if rise > 0.0 {
+            remaining -= up * rise;
+        }
+    }
+
+    // Detects the ground right below the character, or further when snapping.
+    let moving_up = controller.translation.dot(&up) > f32::EPSILON;
+    if !moving_up {
+        let snap_distance = match controller.snap_to_ground {
+            Some(distance) if was_grounded || output.grounded => distance,
+            _ => 0.0,
+        };
+        let probe_distance = snap_distance + controller.offset * 2.0;
+        if let Some(hit) = cast(&position, &-up, probe_distance) {
+            if is_ground(&hit.normal) {
+                position.translation.vector -= up * (hit.toi - controller.offset).max(0.0);
+                output.grounded = true;
+                output.ground = Some(hit.entity);
+            }
+        }
+    }
+
+    output.effective_translation = position.translation.vector - start.translation.vector;
+    output
+}
+
+/// Tries to climb the obstacle in front of the character: moves it up by the step
+/// height, forward, then back down on the obstacle, which has to be ground.
+fn step_up(
+    cast: &impl Fn(&Isometry<f32>, &Vector<f32>, f32) -> Option<RayHit>,
+    controller: &CharacterController,
+    up: &Vector<f32>,
+    is_ground: &impl Fn(&Vector<f32>) -> bool,
+    step_height: f32,
+    position: &Isometry<f32>,
+    remaining: &Vector<f32>,
+) -> Option<(Isometry<f32>, Vector<f32>, EntityId)> {
+    let horizontal = remaining - up * remaining.dot(up);
+    let (forward, forward_distance) = Unit::try_new_and_get(horizontal, f32::EPSILON)?;
+
+    let rise = match cast(position, up, step_height + controller.offset) {
+        Some(hit) => (hit.toi - controller.offset).max(0.0),
+        None => step_height,
+    };
+    if rise <= f32::EPSILON {
+        return None;
+    }
+    let mut stepped = *position;
+    stepped.translation.vector += up * rise;
+
+    let advance = match cast(&stepped, &forward, forward_distance + controller.offset) {
+        Some(hit) => (hit.toi - controller.offset).clamp(0.0, forward_distance),
+        None => forward_distance,
+    };
+    if advance <= f32::EPSILON {
+        return None;
+    }
+    stepped.translation.vector += forward.into_inner() * advance;
+
+    // Without ground below, this is a ledge rather than a step.
+    let landing = cast(&stepped, &-up, rise + controller.offset)?;
+    if !is_ground(&landing.normal) {
+        return None;
+    }
+    stepped.translation.vector -= up * (landing.toi - controller.offset).max(0.0);
+
+    Some((
+        stepped,
+        forward.into_inner() * (forward_distance - advance),
+        landing.entity,
+    ))
+}
+
+#[test]
+fn test_move_characters_system() {
+    use crate::physics::{
+        create_body_and_collider_system, step_world_system, PhysicsSetup, PhysicsTransform,
+    };
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::ColliderBuilder;
+    use rapier::math::Translation;
+    use shipyard::World;
+
+    fn at(x: f32, y: f32) -> Isometry<f32> {
+        Translation::from(Vector::x() * x + Vector::y() * y).into()
+    }
+    #[cfg(feature = "dim2")]
+    fn cuboid(hx: f32, hy: f32) -> ColliderBuilder {
+        ColliderBuilder::cuboid(hx, hy)
+    }
+    #[cfg(feature = "dim3")]
+    fn cuboid(hx: f32, hy: f32) -> ColliderBuilder {
+        ColliderBuilder::cuboid(hx, hy, 10.0)
+    }
+
+    let mut world = World::new();
+    PhysicsSetup::new().install(&world);
+
+    // the ground top is at y = 0, a wall starts at x = 3 and a 0.2 high step at x = 52
+    world.add_entity((
+        RigidBodyBuilder::new_static().position(at(0.0, -0.5)),
+        cuboid(100.0, 0.5),
+    ));
+    world.add_entity((
+        RigidBodyBuilder::new_static().position(at(3.5, 1.0)),
+        cuboid(0.5, 1.0),
+    ));
+    let step = world.add_entity((
+        RigidBodyBuilder::new_static().position(at(56.0, 0.1)),
+        cuboid(4.0, 0.1),
+    ));
+    // walls at x = -17 and x = 23, the second one only in a layer of its own, and a
+    // sensor at x = -47
+    world.add_entity((
+        RigidBodyBuilder::new_static().position(at(-16.5, 1.0)),
+        cuboid(0.5, 1.0),
+    ));
+    world.add_entity((
+        RigidBodyBuilder::new_static().position(at(23.5, 1.0)),
+        cuboid(0.5, 1.0),
+        CollisionLayers::new(0b10, u16::MAX),
+    ));
+    world.add_entity((
+        RigidBodyBuilder::new_static().position(at(-46.5, 1.0)),
+        cuboid(0.5, 1.0).sensor(true),
+    ));
+
+    let walker = world.add_entity((
+        RigidBodyBuilder::new_kinematic().position(at(-50.0, 0.51)),
+        ColliderBuilder::ball(0.5),
+        CharacterController::default(),
+    ));
+    let blocked = world.add_entity((
+        RigidBodyBuilder::new_kinematic().position(at(0.0, 0.51)),
+        ColliderBuilder::ball(0.5),
+        CharacterController::default(),
+    ));
+    let climber = world.add_entity((
+        RigidBodyBuilder::new_kinematic().position(at(50.0, 0.51)),
+        ColliderBuilder::ball(0.5),
+        CharacterController::default().with_step_height(0.3),
+        CharacterControllerOutput {
+            grounded: true,
+            ..Default::default()
+        },
+    ));
+    let parent = world.add_entity((
+        RigidBodyBuilder::new_kinematic().position(at(-20.0, 0.51)),
+        CharacterController::default(),
+    ));
+    world.add_entity((ColliderBuilder::ball(0.5), ColliderParent(parent)));
+    let ghost = world.add_entity((
+        RigidBodyBuilder::new_kinematic().position(at(20.0, 0.51)),
+        ColliderBuilder::ball(0.5),
+        CharacterController::default(),
+        CollisionLayers::new(0b01, 0b01),
+    ));
+    world.run(create_body_and_collider_system).unwrap();
+    world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+
+    {
+        let mut controllers = world.borrow::<ViewMut<CharacterController>>().unwrap();
+        (&mut controllers).get(walker).unwrap().translation = Vector::x() * 5.0 - Vector::y() * 0.1;
+        (&mut controllers).get(blocked).unwrap().translation = Vector::x() * 5.0;
+        (&mut controllers).get(climber).unwrap().translation = Vector::x() * 5.0;
+        (&mut controllers).get(parent).unwrap().translation = Vector::x() * 5.0;
+        (&mut controllers).get(ghost).unwrap().translation = Vector::x() * 5.0;
+    }
+    world.run(move_characters_system).unwrap();
+
+    let outputs = world.borrow::<View<CharacterControllerOutput>>().unwrap();
+    let controllers = world.borrow::<View<CharacterController>>().unwrap();
+
+    // slides along the ground, through the sensor
+    let output = outputs.get(walker).unwrap();
+    assert!(output.grounded);
+    assert!(!output.touching_walls);
+    assert!((output.effective_translation.x - 5.0).abs() < 0.05);
+    assert!(output.effective_translation.y.abs() < 0.02);
+    assert_eq!(
+        controllers.get(walker).unwrap().translation,
+        Vector::zeros()
+    );
+
+    // stops in front of the wall
+    let output = outputs.get(blocked).unwrap();
+    assert!(output.grounded);
+    assert!(output.touching_walls);
+    assert!((output.effective_translation.x - 2.49).abs() < 0.01);
+
+    // casts the colliders attached to its rigid-body
+    let output = outputs.get(parent).unwrap();
+    assert!(output.touching_walls);
+    assert!((output.effective_translation.x - 2.49).abs() < 0.01);
+
+    // goes through the wall it does not interact with
+    let output = outputs.get(ghost).unwrap();
+    assert!(!output.touching_walls);
+    assert!((output.effective_translation.x - 5.0).abs() < 0.01);
+
+    // climbs the step
+    let output = outputs.get(climber).unwrap();
+    assert!(output.grounded);
+    assert_eq!(output.ground, Some(step));
+    assert!((output.effective_translation.x - 5.0).abs() < 0.01);
+    assert!((output.effective_translation.y - 0.2).abs() < 0.01);
+    drop((outputs, controllers));
+
+    // the body reaches the new position at the next step
+    world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+    world.run(crate::physics::sync_transforms_system).unwrap();
+    let transforms = world.borrow::<View<PhysicsTransform>>().unwrap();
+    assert!((transforms.get(climber).unwrap().translation().x - 55.0).abs() < 0.01);
+}
@@ -1,6 +1,7 @@
 use rapier::dynamics::{JointHandle, JointParams, RigidBodyHandle};
-use rapier::geometry::ColliderHandle;
-use rapier::math::{Isometry, Translation, Vector};
+use rapier::geometry::{ColliderHandle, InteractionGroups, SharedShape};
+use rapier::math::{AngVector, Isometry, Point, Translation, Vector};
+use rapier::na;
 #[cfg(feature = "dim2")]
 use rapier::na::UnitComplex;
 #[cfg(feature = "dim3")]
@@ -48,6 +49,161 @@ impl ColliderHandleComponent {
     }
 }
 
+/// A component attaching the `ColliderBuilder` of its entity to the rigid-body of
+/// another entity, to build compound colliders.
+///
+/// The collider is created by `create_body_and_collider_system` once the parent's
+/// rigid-body exists. If the parent entity is deleted before that, the builder is
+/// discarded and reported to `PhysicsDiagnostics`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColliderParent(pub EntityId);
+
+/// A named layer of `CollisionLayers`, usually a fieldless enum:
+///
+/// ```ignore
+/// #[derive(Clone, Copy)]
+/// enum Layer {
+///     World,
+///     Player,
+///     Enemy,
+/// }
+///
+/// impl PhysicsLayer for Layer {
+///     fn to_bits(self) -> u16 {
+///         1 << self as u16
+///     }
+/// }
+/// ```
+pub trait PhysicsLayer: Copy {
+    /// The bit of this layer, one of the 16 available.
+    fn to_bits(self) -> u16;
+}
+
+/// A component describing the layers an entity's colliders are members of, and the
+/// layers they interact with.
+///
+/// Two colliders interact if each one is a member of a layer in the filter of the
+/// other. Colliders that do not interact get no contacts and no intersections. The solver
+/// memberships and filter are tested the same way, but only decide whether colliders in
+/// contact exert forces on each other: their contacts are still reported by the `EventQueue`.
+///
+/// The layers of a collider are read from its own entity, or else from the entity of
+/// its rigid-body, and set as its collision and solver groups by
+/// `create_body_and_collider_system`. Colliders without layers keep the groups of their
+/// `ColliderBuilder`.
+///
+/// Rapier 0.6 cannot change the groups of an existing collider, so layers added or modified
+/// afterwards are collected by `apply_physics_changes_system` in the
+/// `ChangedCollisionLayers` and applied by a physics hook on every step, until the collider
+/// is recreated. The hook can only remove interactions: a collider does not interact with
+/// layers its groups did not accept when it was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionLayers {
+    /// The bits of the layers the colliders are members of.
+    pub memberships: u16,
+    /// The bits of the layers the colliders interact with.
+    pub filter: u16,
+    /// The bits of the layers the colliders are members of for the contact forces.
+    pub solver_memberships: u16,
+    /// The bits of the layers the colliders exert contact forces on.
+    pub solver_filter: u16,
+}
+
+impl CollisionLayers {
+    /// Layers from raw bits, exerting contact forces on every collider they interact with.
+    pub const fn new(memberships: u16, filter: u16) -> Self {
+        Self {
+            memberships,
+            filter,
+            solver_memberships: u16::MAX,
+            solver_filter: u16::MAX,
+        }
+    }
+
+    /// Member of every layer and interacting with every layer.
+    pub const fn all() -> Self {
+        Self::new(u16::MAX, u16::MAX)
+    }
+
+    /// Member of no layer and interacting with no layer.
+    pub const fn none() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// Layers from named memberships and filter.
+    pub fn from_layers<L: PhysicsLayer>(memberships: &[L], filter: &[L]) -> Self {
+        Self::new(layer_bits(memberships), layer_bits(filter))
+    }
+
+    /// Adds a layer to the memberships.
+    pub fn with_membership(mut self, layer: impl PhysicsLayer) -> Self {
+        self.memberships |= layer.to_bits();
+        self
+    }
+
+    /// Removes a layer from the memberships.
+    pub fn without_membership(mut self, layer: impl PhysicsLayer) -> Self {
+        self.memberships &= !layer.to_bits();
+        self
+    }
+
+    /// Adds a layer to the filter.
+    pub fn with_filter(mut self, layer: impl PhysicsLayer) -> Self {
+        self.filter |= layer.to_bits();
+        self
+    }
+
+    /// Removes a layer from the filter.
+    pub fn without_filter(mut self, layer: impl PhysicsLayer) -> Self {
+        self.filter &= !layer.to_bits();
+        self
+    }
+
+    /// Replaces the solver memberships and filter with named layers.
+    pub fn with_solver_layers<L: PhysicsLayer>(mut self, memberships: &[L], filter: &[L]) -> Self {
+        self.solver_memberships = layer_bits(memberships);
+        self.solver_filter = layer_bits(filter);
+        self
+    }
+
+    /// Whether colliders with these layers and colliders with the `other` layers interact.
+    pub fn interacts_with(&self, other: &CollisionLayers) -> bool {
+        self.collision_groups().test(other.collision_groups())
+    }
+
+    /// Whether colliders with these layers and colliders with the `other` layers exert
+    /// contact forces on each other, when they interact.
+    pub fn solves_with(&self, other: &CollisionLayers) -> bool {
+        self.solver_groups().test(other.solver_groups())
+    }
+
+    /// The collision groups of the colliders.
+    pub fn collision_groups(&self) -> InteractionGroups {
+        InteractionGroups::new(self.memberships, self.filter)
+    }
+
+    /// The solver groups of the colliders.
+    pub fn solver_groups(&self) -> InteractionGroups {
+        InteractionGroups::new(self.solver_memberships, self.solver_filter)
+    }
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl From<CollisionLayers> for InteractionGroups {
+    fn from(layers: CollisionLayers) -> Self {
+        layers.collision_groups()
+    }
+}
+
+fn layer_bits<L: PhysicsLayer>(layers: &[L]) -> u16 {
+    layers.iter().fold(0, |bits, layer| bits | layer.to_bits())
+}
+
 /// A component representing a joint added to the JointSet resource.
 ///
 /// This component should not be created manually. It is automatically created and
@@ -88,10 +244,13 @@ impl JointHandleComponent {
 ///
 /// This is a transient component that will be automatically replaced by a `JointHandleComponent`
 /// once the Rapier joint it describes has been created and added to the `JointSet` resource.
+/// The joint stays pending until both entities have a rigid-body.
 pub struct JointBuilderComponent {
     pub(crate) params: JointParams,
     pub(crate) entity1: EntityId,
     pub(crate) entity2: EntityId,
+    pub(crate) timeout: Option<u32>,
+    pub(crate) attempts: u32,
 }
 
 impl JointBuilderComponent {
@@ -104,20 +263,26 @@ impl JointBuilderComponent {
             params: joint.into(),
             entity1,
             entity2,
+            timeout: None,
+            attempts: 0,
         }
     }
+
+    /// Sets the maximum number of runs of `create_joints_system` this joint can stay
+    /// pending for, waiting for the rigid-bodies of its entities to be created.
+    ///
+    /// Once exceeded, the builder is discarded and reported to `PhysicsDiagnostics`.
+    pub fn timeout(mut self, runs: u32) -> Self {
+        self.timeout = Some(runs);
+        self
+    }
 }
 
 /// A component to store the previous position of a body to use for
 /// interpolation between steps
+#[derive(Default)]
 pub struct PhysicsInterpolationComponent(pub Option<Isometry<f32>>);
 
-impl Default for PhysicsInterpolationComponent {
-    fn default() -> Self {
-        PhysicsInterpolationComponent(None)
-    }
-}
-
 impl PhysicsInterpolationComponent {
     /// Create a new PhysicsInterpolationComponent from a translation and rotation
     #[cfg(feature = "dim2")]
@@ -137,3 +302,301 @@ impl PhysicsInterpolationComponent {
         )))
     }
 }
+
+/// A component holding the position of an entity's rigid-body.
+///
+/// It is automatically added to every entity whose rigid-body is created by
+/// `create_body_and_collider_system`, and updated by `sync_transforms_system`
+/// after each physics step. The translation is expressed in world units, that is
+/// the Rapier translation multiplied by `RapierConfiguration::scale`.
+///
+/// Modifying this component teleports the rigid-body (or sets the next target
+/// of a kinematic body) when `apply_physics_changes_system` runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsTransform(pub Isometry<f32>);
+
+impl PhysicsTransform {
+    /// Create a new `PhysicsTransform` from a Rapier position and the
+    /// `RapierConfiguration::scale` factor.
+    pub fn from_physics(position: &Isometry<f32>, scale: f32) -> Self {
+        let mut position = *position;
+        position.translation.vector *= scale;
+        Self(position)
+    }
+
+    /// The position converted back to Rapier units.
+    pub fn to_physics(&self, scale: f32) -> Isometry<f32> {
+        let mut position = self.0;
+        position.translation.vector /= scale;
+        position
+    }
+
+    /// The translation, in world units.
+    pub fn translation(&self) -> Vector<f32> {
+        self.0.translation.vector
+    }
+}
+
+/// A component holding the position of an entity's rigid-body interpolated between
+/// the last two physics steps, for rendering.
+///
+/// It is added by `interpolate_transforms_system` to every entity with a
+/// `PhysicsInterpolationComponent`. Like `PhysicsTransform`, the translation is
+/// expressed in world units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterpolatedTransform(pub Isometry<f32>);
+
+impl InterpolatedTransform {
+    /// The position converted back to Rapier units.
+    pub fn to_physics(&self, scale: f32) -> Isometry<f32> {
+        let mut position = self.0;
+        position.translation.vector /= scale;
+        position
+    }
+
+    /// The translation, in world units.
+    pub fn translation(&self) -> Vector<f32> {
+        self.0.translation.vector
+    }
+}
+
+/// A component holding the velocity of an entity's rigid-body.
+///
+/// Entities opt into this component: once added, it is kept up to date by
+/// `sync_transforms_system`, and modifying it sets the rigid-body velocity
+/// when `apply_physics_changes_system` runs. The linear velocity is expressed
+/// in world units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Velocity {
+    /// The linear velocity.
+    pub linvel: Vector<f32>,
+    /// The angular velocity.
+    pub angvel: AngVector<f32>,
+}
+
+impl Default for Velocity {
+    fn default() -> Self {
+        Self {
+            linvel: na::zero(),
+            angvel: na::zero(),
+        }
+    }
+}
+
+/// A component describing a force and torque continuously applied to an
+/// entity's rigid-body, at each physics step, as long as the component is present.
+///
+/// The force is expressed in world units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExternalForce {
+    /// The force applied at the rigid-body center of mass.
+    pub force: Vector<f32>,
+    /// The torque applied to the rigid-body.
+    pub torque: AngVector<f32>,
+}
+
+impl Default for ExternalForce {
+    fn default() -> Self {
+        Self {
+            force: na::zero(),
+            torque: na::zero(),
+        }
+    }
+}
+
+/// A component describing an impulse applied once to an entity's rigid-body.
+///
+/// `apply_physics_changes_system` applies it and resets it to zero.
+/// The impulse is expressed in world units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExternalImpulse {
+    /// The impulse applied at the rigid-body center of mass.
+    pub impulse: Vector<f32>,
+    /// The torque impulse applied to the rigid-body.
+    pub torque_impulse: AngVector<f32>,
+}
+
+impl Default for ExternalImpulse {
+    fn default() -> Self {
+        Self {
+            impulse: na::zero(),
+            torque_impulse: na::zero(),
+        }
+    }
+}
+
+/// A single hit of a `RayCaster` or a `ShapeCaster`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// The entity owning the collider that was hit.
+    pub entity: EntityId,
+    /// The hit point on the collider, in world units.
+    pub point: Point<f32>,
+    /// The normal of the collider at the hit point.
+    pub normal: Vector<f32>,
+    /// The distance travelled by the ray or the shape before the hit, in world units.
+    pub toi: f32,
+}
+
+/// A component holding the hits of its entity's `RayCaster`, sorted by increasing distance.
+///
+/// It is added and updated by `update_casters_system`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RayHits(pub Vec<RayHit>);
+
+impl RayHits {
+    /// The closest hit, if any.
+    pub fn first(&self) -> Option<&RayHit> {
+        self.0.first()
+    }
+
+    /// Iterates over the hits, from the closest to the farthest.
+    pub fn iter(&self) -> impl Iterator<Item = &RayHit> {
+        self.0.iter()
+    }
+
+    /// Whether nothing was hit.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A component holding the hits of its entity's `ShapeCaster`, sorted by increasing distance.
+///
+/// It is added and updated by `update_casters_system`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShapeHits(pub Vec<RayHit>);
+
+impl ShapeHits {
+    /// The closest hit, if any.
+    pub fn first(&self) -> Option<&RayHit> {
+        self.0.first()
+    }
+
+    /// Iterates over the hits, from the closest to the farthest.
+    pub fn iter(&self) -> impl Iterator<Item = &RayHit> {
+        self.0.iter()
+    }
+
+    /// Whether nothing was hit.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A component casting a ray from its entity after each physics step, the hits
+/// being written to the entity's `RayHits` by `update_casters_system`.
+///
+/// The origin and direction are relative to the position of the entity's rigid-body, or
+/// else of its collider, or to the world if it has neither, and distances are expressed in
+/// world units. Only the colliders whose collision groups interact with `groups`, and with
+/// the `CollisionLayers` of the entity if it has some, are hit, except the ones of the
+/// caster entity itself and of its child colliders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayCaster {
+    /// The origin of the ray.
+    pub origin: Point<f32>,
+    /// The direction of the ray, it does not need to be normalized.
+    pub direction: Vector<f32>,
+    /// The maximum distance travelled by the ray.
+    pub max_distance: f32,
+    /// Whether a ray starting inside a collider hits it right away, or hits its boundary.
+    pub solid: bool,
+    /// The collision groups of the ray.
+    pub groups: InteractionGroups,
+    /// The maximum number of hits recorded.
+    pub max_hits: usize,
+}
+
+impl RayCaster {
+    /// A solid ray cast from the entity origin, recording the closest hit with any collider.
+    pub fn new(direction: Vector<f32>, max_distance: f32) -> Self {
+        Self {
+            origin: Point::origin(),
+            direction,
+            max_distance,
+            solid: true,
+            groups: InteractionGroups::all(),
+            max_hits: 1,
+        }
+    }
+
+    /// Sets the origin of the ray.
+    pub fn with_origin(mut self, origin: Point<f32>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Sets whether the ray is solid.
+    pub fn with_solid(mut self, solid: bool) -> Self {
+        self.solid = solid;
+        self
+    }
+
+    /// Sets the collision groups of the ray.
+    pub fn with_groups(mut self, groups: InteractionGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Sets the maximum number of hits recorded.
+    pub fn with_max_hits(mut self, max_hits: usize) -> Self {
+        self.max_hits = max_hits;
+        self
+    }
+}
+
+/// A component casting a shape from its entity after each physics step, the hits
+/// being written to the entity's `ShapeHits` by `update_casters_system`.
+///
+/// The origin, direction and colliders hit are the same as for a `RayCaster`, and the
+/// shape is expressed in world units too, like the shapes of `PhysicsQuery`. The hit point
+/// and normal are those of the collider hit, when the shape touches it. Sensors are not
+/// hit by the shape, and a shape `PhysicsQuery` cannot scale to Rapier units hits nothing.
+#[derive(Clone)]
+pub struct ShapeCaster {
+    /// The shape to cast.
+    pub shape: SharedShape,
+    /// The initial position of the shape.
+    pub origin: Isometry<f32>,
+    /// The direction of the cast, it does not need to be normalized.
+    pub direction: Vector<f32>,
+    /// The maximum distance travelled by the shape.
+    pub max_distance: f32,
+    /// The collision groups of the shape.
+    pub groups: InteractionGroups,
+    /// The maximum number of hits recorded.
+    pub max_hits: usize,
+}
+
+impl ShapeCaster {
+    /// A shape cast from the entity origin, recording the closest hit with any collider.
+    pub fn new(shape: SharedShape, direction: Vector<f32>, max_distance: f32) -> Self {
+        Self {
+            shape,
+            origin: Isometry::identity(),
+            direction,
+            max_distance,
+            groups: InteractionGroups::all(),
+            max_hits: 1,
+        }
+    }
+
+    /// Sets the initial position of the shape.
+    pub fn with_origin(mut self, origin: Isometry<f32>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Sets the collision groups of the shape.
+    pub fn with_groups(mut self, groups: InteractionGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Sets the maximum number of hits recorded.
+    pub fn with_max_hits(mut self, max_hits: usize) -> Self {
+        self.max_hits = max_hits;
+        self
+    }
+}
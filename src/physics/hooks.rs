@@ -0,0 +1,536 @@
+use crate::physics::{ChangedCollisionLayers, CollisionLayers, EntityMaps};
+use crate::rapier::geometry::{Collider, ColliderHandle, InteractionGroups, SolverFlags};
+use crate::rapier::pipeline::{
+    ContactModificationContext, PairFilterContext, PhysicsHooks, PhysicsHooksFlags,
+};
+
+use shipyard::{error, AllStorages, EntityId, Get, View};
+
+/// Physics hooks expressed in terms of shipyard entities and components.
+///
+/// The hooks are run by `step_world_with_entity_hooks_system`, which borrows the `Views`
+/// from the World once per call, and every hook receives them along with the entities
+/// owning the two colliders involved. The step fails with an error if they borrow a storage
+/// the step borrows mutably, like the `RigidBodySet`.
+///
+/// The generic associated type requires Rust 1.65.
+///
+/// ```ignore
+/// struct SameTeamFilter;
+///
+/// impl EntityPhysicsHooks for SameTeamFilter {
+///     type Views<'a> = View<'a, Team>;
+///
+///     fn borrow_views(all_storages: &AllStorages) -> Result<View<'_, Team>, error::GetStorage> {
+///         all_storages.borrow::<View<Team>>()
+///     }
+///
+///     fn active_hooks(&self) -> PhysicsHooksFlags {
+///         PhysicsHooksFlags::FILTER_CONTACT_PAIR
+///     }
+///
+///     fn filter_contact_pair(
+///         &self,
+///         teams: &View<Team>,
+///         entity1: EntityId,
+///         entity2: EntityId,
+///         _context: &PairFilterContext,
+///     ) -> Option<SolverFlags> {
+///         if teams.get(entity1).ok() != teams.get(entity2).ok() {
+///             Some(SolverFlags::COMPUTE_IMPULSES)
+///         } else {
+///             None
+///         }
+///     }
+/// }
+/// ```
+pub trait EntityPhysicsHooks: Send + Sync + 'static {
+    /// Read-only views used by the hooks, like `View<'a, T>` or a tuple of views.
+    type Views<'a>: Send + Sync;
+
+    /// Borrows the views from the World.
+    fn borrow_views(all_storages: &AllStorages) -> Result<Self::Views<'_>, error::GetStorage>;
+
+    /// Which hook methods are called by the physics pipeline.
+    fn active_hooks(&self) -> PhysicsHooksFlags;
+
+    /// Applies the contact pair filter, see `PhysicsHooks::filter_contact_pair`.
+    fn filter_contact_pair(
+        &self,
+        _views: &Self::Views<'_>,
+        _entity1: EntityId,
+        _entity2: EntityId,
+        _context: &PairFilterContext,
+    ) -> Option<SolverFlags> {
+        None
+    }
+
+    /// Applies the intersection pair filter, see `PhysicsHooks::filter_intersection_pair`.
+    fn filter_intersection_pair(
+        &self,
+        _views: &Self::Views<'_>,
+        _entity1: EntityId,
+        _entity2: EntityId,
+        _context: &PairFilterContext,
+    ) -> bool {
+        false
+    }
+
+    /// Modifies the set of contacts seen by the constraints solver, see
+    /// `PhysicsHooks::modify_solver_contacts`.
+    fn modify_solver_contacts(
+        &self,
+        _views: &Self::Views<'_>,
+        _entity1: EntityId,
+        _entity2: EntityId,
+        _context: &mut ContactModificationContext,
+    ) {
+    }
+}
+
+/// `EntityPhysicsHooks` with their views erased, so they can be stored in `UserPhysicsHooks`.
+pub(crate) trait BorrowPhysicsHooks: Send + Sync {
+    /// Borrows the views and calls `f` with hooks usable by the physics pipeline.
+    fn with_hooks(
+        &self,
+        all_storages: &AllStorages,
+        entity_maps: &EntityMaps,
+        f: &mut dyn FnMut(&dyn PhysicsHooks) -> Result<(), error::Run>,
+    ) -> Result<(), error::Run>;
+}
+
+impl<H: EntityPhysicsHooks> BorrowPhysicsHooks for H {
+    fn with_hooks(
+        &self,
+        all_storages: &AllStorages,
+        entity_maps: &EntityMaps,
+        f: &mut dyn FnMut(&dyn PhysicsHooks) -> Result<(), error::Run>,
+    ) -> Result<(), error::Run> {
+        f(&EntityHooksAdapter {
+            hooks: self,
+            views: H::borrow_views(all_storages)?,
+            entity_maps,
+        })
+    }
+}
+
+/// Forwards the Rapier hooks to `EntityPhysicsHooks`.
+///
+/// Pairs involving a collider unknown to the `EntityMaps` get Rapier's default
+/// filtering: only pairs involving a dynamic rigid-body interact.
+struct EntityHooksAdapter<'a, H: EntityPhysicsHooks> {
+    hooks: &'a H,
+    views: H::Views<'a>,
+    entity_maps: &'a EntityMaps,
+}
+
+impl<H: EntityPhysicsHooks> EntityHooksAdapter<'_, H> {
+    fn entities(
+        &self,
+        collider1: ColliderHandle,
+        collider2: ColliderHandle,
+    ) -> Option<(EntityId, EntityId)> {
+        Some((
+            self.entity_maps.collider_entity(collider1)?,
+            self.entity_maps.collider_entity(collider2)?,
+        ))
+    }
+}
+
+fn is_dynamic_pair(context: &PairFilterContext) -> bool {
+    context.rigid_body1.is_dynamic() || context.rigid_body2.is_dynamic()
+}
+
+impl<H: EntityPhysicsHooks> PhysicsHooks for EntityHooksAdapter<'_, H> {
+    fn active_hooks(&self) -> PhysicsHooksFlags {
+        self.hooks.active_hooks()
+    }
+
+    fn filter_contact_pair(&self, context: &PairFilterContext) -> Option<SolverFlags> {
+        match self.entities(context.collider_handle1, context.collider_handle2) {
+            Some((entity1, entity2)) => {
+                self.hooks
+                    .filter_contact_pair(&self.views, entity1, entity2, context)
+            }
+            None if is_dynamic_pair(context) => Some(SolverFlags::COMPUTE_IMPULSES),
+            None => None,
+        }
+    }
+
+    fn filter_intersection_pair(&self, context: &PairFilterContext) -> bool {
+        match self.entities(context.collider_handle1, context.collider_handle2) {
+            Some((entity1, entity2)) => {
+                self.hooks
+                    .filter_intersection_pair(&self.views, entity1, entity2, context)
+            }
+            None => is_dynamic_pair(context),
+        }
+    }
+
+    fn modify_solver_contacts(&self, context: &mut ContactModificationContext) {
+        if let Some((entity1, entity2)) =
+            self.entities(context.collider_handle1, context.collider_handle2)
+        {
+            self.hooks
+                .modify_solver_contacts(&self.views, entity1, entity2, context)
+        }
+    }
+}
+
+/// Applies the `CollisionLayers` changed since the creation of the colliders on top of
+/// the user hooks.
+pub(crate) struct CollisionLayersHooks<'a> {
+    pub(crate) hooks: &'a dyn PhysicsHooks,
+    pub(crate) layers: &'a View<'a, CollisionLayers>,
+    pub(crate) entity_maps: &'a EntityMaps,
+    pub(crate) changed_layers: &'a ChangedCollisionLayers,
+}
+
+impl CollisionLayersHooks<'_> {
+    /// Whether the layers of a collider differ from the groups it was created with.
+    pub(crate) fn layers_changed<'l>(
+        layers: impl Get<Out = &'l CollisionLayers> + Copy,
+        entity_maps: &EntityMaps,
+        handle: ColliderHandle,
+        collider: &Collider,
+    ) -> bool {
+        collider_layers(layers, entity_maps, handle, collider).map_or(false, |layers| {
+            layers.collision_groups() != collider.collision_groups()
+                || layers.solver_groups() != collider.solver_groups()
+        })
+    }
+
+    /// The collision and solver groups of a collider, from its layers if they changed.
+    fn groups(
+        &self,
+        handle: ColliderHandle,
+        collider: &Collider,
+    ) -> (InteractionGroups, InteractionGroups) {
+        let layers = if self.changed_layers.contains(handle) {
+            collider_layers(self.layers, self.entity_maps, handle, collider)
+        } else {
+            None
+        };
+        match layers {
+            Some(layers) => (layers.collision_groups(), layers.solver_groups()),
+            None => (collider.collision_groups(), collider.solver_groups()),
+        }
+    }
+
+    /// Whether the two colliders interact, and whether they exert contact forces on each other.
+    fn interact(&self, context: &PairFilterContext) -> (bool, bool) {
+        let (collision1, solver1) = self.groups(context.collider_handle1, context.collider1);
+        let (collision2, solver2) = self.groups(context.collider_handle2, context.collider2);
+        (collision1.test(collision2), solver1.test(solver2))
+    }
+}
+
+/// The layers of a collider, read from its own entity or else from the entity of its body.
+fn collider_layers<'l>(
+    layers: impl Get<Out = &'l CollisionLayers> + Copy,
+    entity_maps: &EntityMaps,
+    handle: ColliderHandle,
+    collider: &Collider,
+) -> Option<CollisionLayers> {
+    let collider_entity = entity_maps.collider_entity(handle);
+    let body_entity = entity_maps.body_entity(collider.parent());
+    collider_entity
+        .and_then(|entity| layers.get(entity).ok())
+        .or_else(|| body_entity.and_then(|entity| layers.get(entity).ok()))
+        .copied()
+}
+
+impl PhysicsHooks for CollisionLayersHooks<'_> {
+    fn active_hooks(&self) -> PhysicsHooksFlags {
+        self.hooks.active_hooks()
+            | PhysicsHooksFlags::FILTER_CONTACT_PAIR
+            | PhysicsHooksFlags::FILTER_INTERSECTION_PAIR
+    }
+
+    fn filter_contact_pair(&self, context: &PairFilterContext) -> Option<SolverFlags> {
+        let active_hooks = self.hooks.active_hooks();
+        let mut solver_flags = if active_hooks.contains(PhysicsHooksFlags::FILTER_CONTACT_PAIR) {
+            self.hooks.filter_contact_pair(context)?
+        } else if is_dynamic_pair(context) {
+            // The solver flags of the colliders are private, so the contact modification
+            // is requested for every pair when the user hooks modify contacts.
+            let mut solver_flags = SolverFlags::COMPUTE_IMPULSES;
+            if active_hooks.contains(PhysicsHooksFlags::MODIFY_SOLVER_CONTACTS) {
+                solver_flags |= SolverFlags::MODIFY_SOLVER_CONTACTS;
+            }
+            solver_flags
+        } else {
+            return None;
+        };
+
+        // The contacts are kept up to date rather than filtered out, Rapier would
+        // otherwise keep solving the last contacts of a pair that stopped interacting.
+        if self.interact(context) != (true, true) {
+            solver_flags.remove(SolverFlags::COMPUTE_IMPULSES);
+        }
+        Some(solver_flags)
+    }
+
+    fn filter_intersection_pair(&self, context: &PairFilterContext) -> bool {
+        if !self.interact(context).0 {
+            false
+        } else if self
+            .hooks
+            .active_hooks()
+            .contains(PhysicsHooksFlags::FILTER_INTERSECTION_PAIR)
+        {
+            self.hooks.filter_intersection_pair(context)
+        } else {
+            is_dynamic_pair(context)
+        }
+    }
+
+    fn modify_solver_contacts(&self, context: &mut ContactModificationContext) {
+        if self
+            .hooks
+            .active_hooks()
+            .contains(PhysicsHooksFlags::MODIFY_SOLVER_CONTACTS)
+        {
+            self.hooks.modify_solver_contacts(context)
+        }
+    }
+}
+
+#[test]
+fn test_entity_physics_hooks() {
+    use crate::physics::{
+        step_world_with_entity_hooks_system, PhysicsDiagnostic, PhysicsDiagnostics, PhysicsSetup,
+        PhysicsTransform,
+    };
+    use crate::rapier::dynamics::RigidBodyBuilder;
+    use crate::rapier::geometry::ColliderBuilder;
+    use crate::rapier::math::{Translation, Vector};
+    use shipyard::{UniqueViewMut, World};
+
+    #[derive(PartialEq)]
+    struct Team(u32);
+
+    struct SameTeamFilter;
+
+    impl EntityPhysicsHooks for SameTeamFilter {
+        type Views<'a> = View<'a, Team>;
+
+        fn borrow_views(all_storages: &AllStorages) -> Result<View<'_, Team>, error::GetStorage> {
+            all_storages.borrow::<View<Team>>()
+        }
+
+        fn active_hooks(&self) -> PhysicsHooksFlags {
+            PhysicsHooksFlags::FILTER_CONTACT_PAIR
+        }
+
+        fn filter_contact_pair(
+            &self,
+            teams: &View<Team>,
+            entity1: EntityId,
+            entity2: EntityId,
+            _context: &PairFilterContext,
+        ) -> Option<SolverFlags> {
+            if teams.get(entity1).ok() != teams.get(entity2).ok() {
+                Some(SolverFlags::COMPUTE_IMPULSES)
+            } else {
+                None
+            }
+        }
+    }
+
+    let mut world = World::new();
+    PhysicsSetup::new()
+        .gravity(Vector::zeros())
+        .entity_hooks(SameTeamFilter)
+        .install(&world);
+
+    // two pairs of overlapping balls, far from each other, pushed apart unless teammates
+    let mut spawn = |x: f32, team: u32| {
+        world.add_entity((
+            RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::x() * x).into()),
+            ColliderBuilder::ball(0.5),
+            Team(team),
+        ))
+    };
+    let teammate = spawn(0.0, 1);
+    spawn(0.5, 1);
+    let opponent = spawn(10.0, 1);
+    spawn(10.5, 2);
+
+    world
+        .run(crate::physics::create_body_and_collider_system)
+        .unwrap();
+    for _ in 0..2 {
+        world
+            .run_with_data(step_world_with_entity_hooks_system, 1.0 / 60.0)
+            .unwrap()
+            .unwrap();
+    }
+    world.run(crate::physics::sync_transforms_system).unwrap();
+    {
+        let transforms = world.borrow::<View<PhysicsTransform>>().unwrap();
+        assert_eq!(transforms.get(teammate).unwrap().translation().x, 0.0);
+        assert!(transforms.get(opponent).unwrap().translation().x < 10.0);
+    }
+
+    // the hooks are skipped by the step that does not borrow the whole World
+    world
+        .run_with_data(crate::physics::step_world_system, 1.0 / 60.0)
+        .unwrap();
+    let diagnostics: Vec<_> = world
+        .borrow::<UniqueViewMut<PhysicsDiagnostics>>()
+        .unwrap()
+        .drain()
+        .collect();
+    assert_eq!(diagnostics, vec![PhysicsDiagnostic::EntityHooksSkipped]);
+}
+
+#[test]
+fn test_entity_physics_hooks_borrow_conflict() {
+    use crate::physics::{
+        step_world_with_entity_hooks_system, PhysicsInterpolationComponent, PhysicsSetup,
+    };
+    use shipyard::World;
+
+    struct InterpolationFilter;
+
+    impl EntityPhysicsHooks for InterpolationFilter {
+        type Views<'a> = View<'a, PhysicsInterpolationComponent>;
+
+        fn borrow_views(
+            all_storages: &AllStorages,
+        ) -> Result<View<'_, PhysicsInterpolationComponent>, error::GetStorage> {
+            all_storages.borrow::<View<PhysicsInterpolationComponent>>()
+        }
+
+        fn active_hooks(&self) -> PhysicsHooksFlags {
+            PhysicsHooksFlags::empty()
+        }
+    }
+
+    let world = World::new();
+    PhysicsSetup::new()
+        .entity_hooks(InterpolationFilter)
+        .install(&world);
+
+    // the step borrows the interpolation components mutably
+    assert!(world
+        .run_with_data(step_world_with_entity_hooks_system, 1.0 / 60.0)
+        .unwrap()
+        .is_err());
+}
+
+#[test]
+fn test_collision_layers() {
+    use crate::physics::{ColliderHandleComponent, PhysicsSetup, PhysicsTransform};
+    use crate::rapier::dynamics::RigidBodyBuilder;
+    use crate::rapier::geometry::{ColliderBuilder, ColliderSet, NarrowPhase};
+    use crate::rapier::math::{Translation, Vector};
+    use shipyard::{UniqueView, ViewMut, World};
+
+    #[derive(Clone, Copy)]
+    enum Layer {
+        Player,
+        Enemy,
+    }
+
+    impl crate::physics::PhysicsLayer for Layer {
+        fn to_bits(self) -> u16 {
+            1 << self as u16
+        }
+    }
+
+    let mut world = World::new();
+    PhysicsSetup::new().gravity(Vector::zeros()).install(&world);
+
+    // a player overlapping an enemy, at some height
+    let mut add_pair = |y: f32, player_layers: CollisionLayers| {
+        let player = world.add_entity((
+            RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::y() * y).into()),
+            ColliderBuilder::ball(0.5),
+            player_layers,
+        ));
+        let enemy = world.add_entity((
+            RigidBodyBuilder::new_dynamic()
+                .position(Translation::from(Vector::x() * 0.5 + Vector::y() * y).into()),
+            ColliderBuilder::ball(0.5),
+            CollisionLayers::from_layers(&[Layer::Enemy], &[Layer::Player]),
+        ));
+        (player, enemy)
+    };
+    let interacting = CollisionLayers::from_layers(&[Layer::Player], &[Layer::Enemy]);
+    let (dodging, dodging_enemy) =
+        add_pair(0.0, CollisionLayers::from_layers(&[Layer::Player], &[]));
+    let (ghost, ghost_enemy) = add_pair(10.0, interacting.with_solver_layers::<Layer>(&[], &[]));
+    let (stopping, _) = add_pair(20.0, interacting);
+    let (pushed, _) = add_pair(30.0, interacting);
+    world
+        .run(crate::physics::create_body_and_collider_system)
+        .unwrap();
+
+    let collider_handles = world.borrow::<View<ColliderHandleComponent>>().unwrap();
+    let collider = |entity| collider_handles.get(entity).unwrap().handle();
+    {
+        let colliders = world.borrow::<UniqueView<ColliderSet>>().unwrap();
+        let dodging_collider = &colliders[collider(dodging)];
+        assert_eq!(
+            dodging_collider.collision_groups(),
+            InteractionGroups::new(0b01, 0)
+        );
+        assert_eq!(dodging_collider.solver_groups(), InteractionGroups::all());
+        assert_eq!(
+            colliders[collider(ghost)].solver_groups(),
+            InteractionGroups::none()
+        );
+    }
+    let contact_pair = |entity1, entity2| {
+        let narrow_phase = world.borrow::<UniqueView<NarrowPhase>>().unwrap();
+        narrow_phase
+            .contact_pair(collider(entity1), collider(entity2))
+            .map_or(false, |pair| pair.has_any_active_contact)
+    };
+
+    // the layers added with the colliders are their groups
+    world
+        .run(crate::physics::apply_physics_changes_system)
+        .unwrap();
+    assert!(world
+        .borrow::<UniqueView<ChangedCollisionLayers>>()
+        .unwrap()
+        .is_empty());
+
+    // the player stops interacting before the first step
+    {
+        let mut layers = world.borrow::<ViewMut<CollisionLayers>>().unwrap();
+        let mut stopping_layers = (&mut layers).get(stopping).unwrap();
+        *stopping_layers = stopping_layers.without_filter(Layer::Enemy);
+        let mut pushed_layers = (&mut layers).get(pushed).unwrap();
+        *pushed_layers = interacting;
+    }
+    world
+        .run(crate::physics::apply_physics_changes_system)
+        .unwrap();
+    {
+        let changed_layers = world
+            .borrow::<UniqueView<ChangedCollisionLayers>>()
+            .unwrap();
+        assert!(changed_layers.contains(collider(stopping)));
+        assert!(!changed_layers.contains(collider(pushed)));
+    }
+
+    for _ in 0..2 {
+        world
+            .run_with_data(crate::physics::step_world_system, 1.0 / 60.0)
+            .unwrap();
+    }
+    world.run(crate::physics::sync_transforms_system).unwrap();
+    let transforms = world.borrow::<View<PhysicsTransform>>().unwrap();
+    let translation = |entity| transforms.get(entity).unwrap().translation();
+
+    assert_eq!(translation(dodging), Vector::zeros());
+    assert!(!contact_pair(dodging, dodging_enemy));
+    assert_eq!(translation(ghost), Vector::y() * 10.0);
+    assert!(contact_pair(ghost, ghost_enemy));
+    assert_eq!(translation(stopping), Vector::y() * 20.0);
+    assert!(translation(pushed).x < 0.0);
+}
@@ -0,0 +1,279 @@
+use rapier::counters::Counters;
+use rapier::dynamics::{JointSet, RigidBodySet};
+use rapier::geometry::{ColliderSet, NarrowPhase};
+use std::collections::VecDeque;
+
+/// The last samples of a measure, one per frame.
+#[derive(Clone, Debug, Default)]
+pub struct RollingStat {
+    samples: VecDeque<f32>,
+}
+
+impl RollingStat {
+    /// The sample of the last frame, zero if there is none.
+    pub fn latest(&self) -> f32 {
+        self.samples.back().copied().unwrap_or(0.0)
+    }
+
+    /// The average of the samples, zero if there is none.
+    pub fn average(&self) -> f32 {
+        if self.samples.is_empty() {
+            0.0
+        } else {
+            self.samples.iter().sum::<f32>() / self.samples.len() as f32
+        }
+    }
+
+    /// The smallest sample, zero if there is none.
+    pub fn min(&self) -> f32 {
+        self.samples.iter().copied().reduce(f32::min).unwrap_or(0.0)
+    }
+
+    /// The largest sample, zero if there is none.
+    pub fn max(&self) -> f32 {
+        self.samples.iter().copied().reduce(f32::max).unwrap_or(0.0)
+    }
+
+    /// Iterates through the samples, from the oldest to the latest.
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().copied()
+    }
+
+    /// Number of samples.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns `true` if there is no sample.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    fn push(&mut self, sample: f32, window: usize) {
+        while self.samples.len() >= window.max(1) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+/// Timings of the steps run during the current frame, in milliseconds.
+#[derive(Clone, Copy, Default)]
+struct StepTimes {
+    step: f32,
+    broad_phase: f32,
+    narrow_phase: f32,
+    island_construction: f32,
+    solver: f32,
+    ccd: f32,
+}
+
+/// A resource collecting the performance counters of the `PhysicsPipeline` each frame,
+/// updated by `step_world_system`. It is only added by `PhysicsSetup::metrics`.
+///
+/// Timings are in milliseconds, summed over the steps of the frame, and kept for the
+/// last `window` frames. Counts describe the physics world at the end of the last frame.
+pub struct PhysicsMetrics {
+    window: usize,
+    current: StepTimes,
+    /// Time elapsed since the previous frame, given to `step_world_system`.
+    pub frame_time: RollingStat,
+    /// Total time spent stepping the physics world.
+    pub step_time: RollingStat,
+    /// Time spent updating the AABBs and finding the new pairs of colliders.
+    pub broad_phase_time: RollingStat,
+    /// Time spent computing the contacts and intersections of the pairs.
+    pub narrow_phase_time: RollingStat,
+    /// Time spent building the islands of active rigid-bodies.
+    pub island_construction_time: RollingStat,
+    /// Time spent solving the constraints and integrating the rigid-bodies.
+    pub solver_time: RollingStat,
+    /// Time spent on continuous collision detection, always zero with Rapier 0.6
+    /// which does not implement it.
+    pub ccd_time: RollingStat,
+    /// Number of steps run during the last frame.
+    pub substeps: usize,
+    /// Number of contact pairs with at least one active contact.
+    pub contact_pairs: usize,
+    /// Number of contacts given to the constraints solver.
+    pub contacts: usize,
+    /// Number of rigid-bodies, including the static ones.
+    pub bodies: usize,
+    /// Number of non-static rigid-bodies that are awake.
+    pub active_bodies: usize,
+    /// Number of non-static rigid-bodies that are sleeping.
+    pub sleeping_bodies: usize,
+    /// Number of colliders.
+    pub colliders: usize,
+    /// Number of joints.
+    pub joints: usize,
+}
+
+impl PhysicsMetrics {
+    /// Number of frames kept by default.
+    pub const DEFAULT_WINDOW: usize = 120;
+
+    /// Creates empty metrics keeping the samples of the last `window` frames.
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            current: StepTimes::default(),
+            frame_time: RollingStat::default(),
+            step_time: RollingStat::default(),
+            broad_phase_time: RollingStat::default(),
+            narrow_phase_time: RollingStat::default(),
+            island_construction_time: RollingStat::default(),
+            solver_time: RollingStat::default(),
+            ccd_time: RollingStat::default(),
+            substeps: 0,
+            contact_pairs: 0,
+            contacts: 0,
+            bodies: 0,
+            active_bodies: 0,
+            sleeping_bodies: 0,
+            colliders: 0,
+            joints: 0,
+        }
+    }
+
+    /// Number of frames kept.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Starts a new frame, the steps recorded from now on are part of it.
+    pub(crate) fn begin_frame(&mut self) {
+        self.current = StepTimes::default();
+        self.substeps = 0;
+    }
+
+    /// Adds the timings of a step to the current frame.
+    pub(crate) fn record_step(&mut self, counters: &Counters) {
+        self.current.step += counters.step_time() as f32;
+        self.current.broad_phase += counters.cd.broad_phase_time.time() as f32;
+        self.current.narrow_phase += counters.cd.narrow_phase_time.time() as f32;
+        self.current.island_construction += counters.stages.island_construction_time.time() as f32;
+        self.current.solver += counters.stages.solver_time.time() as f32;
+        self.current.ccd += counters.stages.ccd_time.time() as f32;
+        self.substeps += 1;
+    }
+
+    /// Pushes the samples of the current frame and counts the content of the physics world.
+    pub(crate) fn end_frame(
+        &mut self,
+        delta_seconds: f32,
+        narrow_phase: &NarrowPhase,
+        bodies: &RigidBodySet,
+        colliders: &ColliderSet,
+        joints: &JointSet,
+    ) {
+        let window = self.window;
+        let current = self.current;
+        self.frame_time.push(delta_seconds * 1000.0, window);
+        self.step_time.push(current.step, window);
+        self.broad_phase_time.push(current.broad_phase, window);
+        self.narrow_phase_time.push(current.narrow_phase, window);
+        self.island_construction_time
+            .push(current.island_construction, window);
+        self.solver_time.push(current.solver, window);
+        self.ccd_time.push(current.ccd, window);
+
+        self.contact_pairs = 0;
+        self.contacts = 0;
+        for pair in narrow_phase.contact_pairs() {
+            if pair.has_any_active_contact {
+                self.contact_pairs += 1;
+                self.contacts += pair
+                    .manifolds
+                    .iter()
+                    .map(|manifold| manifold.data.solver_contacts.len())
+                    .sum::<usize>();
+            }
+        }
+
+        self.bodies = bodies.len();
+        self.active_bodies = 0;
+        self.sleeping_bodies = 0;
+        for (_, body) in bodies.iter().filter(|(_, body)| !body.is_static()) {
+            if body.is_sleeping() {
+                self.sleeping_bodies += 1;
+            } else {
+                self.active_bodies += 1;
+            }
+        }
+        self.colliders = colliders.len();
+        self.joints = joints.len();
+    }
+}
+
+impl Default for PhysicsMetrics {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_WINDOW)
+    }
+}
+
+#[test]
+fn test_rolling_stat() {
+    let mut stat = RollingStat::default();
+    assert_eq!(
+        (stat.latest(), stat.average(), stat.min(), stat.max()),
+        (0.0, 0.0, 0.0, 0.0)
+    );
+
+    for sample in &[4.0, 1.0, 3.0, 2.0] {
+        stat.push(*sample, 3);
+    }
+    assert_eq!(stat.samples().collect::<Vec<_>>(), vec![1.0, 3.0, 2.0]);
+    assert_eq!(stat.latest(), 2.0);
+    assert_eq!(stat.average(), 2.0);
+    assert_eq!(stat.min(), 1.0);
+    assert_eq!(stat.max(), 3.0);
+}
+
+#[test]
+fn test_physics_metrics() {
+    use crate::physics::{create_body_and_collider_system, step_world_system, PhysicsSetup};
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::ColliderBuilder;
+    use rapier::math::{Translation, Vector};
+    use shipyard::{UniqueView, World};
+
+    let mut world = World::new();
+    PhysicsSetup::new().metrics(true).install(&world);
+
+    // a ball resting on the ground, and a sleeping ball far from them
+    #[cfg(feature = "dim2")]
+    let ground = ColliderBuilder::cuboid(5.0, 0.1);
+    #[cfg(feature = "dim3")]
+    let ground = ColliderBuilder::cuboid(5.0, 0.1, 5.0);
+    world.add_entity((RigidBodyBuilder::new_static(), ground));
+    world.add_entity((
+        RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::y() * 0.59).into()),
+        ColliderBuilder::ball(0.5),
+    ));
+    world.add_entity((
+        RigidBodyBuilder::new_dynamic()
+            .position(Translation::from(Vector::x() * 10.0).into())
+            .sleeping(true),
+        ColliderBuilder::ball(0.5),
+    ));
+    world.run(create_body_and_collider_system).unwrap();
+    for _ in 0..3 {
+        world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+    }
+
+    let metrics = world.borrow::<UniqueView<PhysicsMetrics>>().unwrap();
+    assert_eq!(metrics.frame_time.len(), 3);
+    assert!((metrics.frame_time.average() - 1000.0 / 60.0).abs() < 1.0e-3);
+    assert_eq!(metrics.step_time.len(), 3);
+    assert!(metrics.step_time.min() > 0.0);
+    assert!(metrics.solver_time.max() <= metrics.step_time.max());
+    assert_eq!(metrics.substeps, 1);
+    assert_eq!(metrics.contact_pairs, 1);
+    assert!(metrics.contacts > 0);
+    assert_eq!(metrics.bodies, 3);
+    assert_eq!(metrics.active_bodies, 1);
+    assert_eq!(metrics.sleeping_bodies, 1);
+    assert_eq!(metrics.colliders, 3);
+    assert_eq!(metrics.joints, 0);
+}
@@ -1,7 +1,19 @@
+pub use self::character::*;
 pub use self::components::*;
+pub use self::hooks::EntityPhysicsHooks;
+pub use self::metrics::*;
+pub use self::plugin::*;
+pub use self::query::*;
 pub use self::resources::*;
+pub use self::setup::*;
 pub use self::systems::*;
 
+pub mod character;
 pub mod components;
+pub mod hooks;
+pub mod metrics;
+pub mod plugin;
+pub mod query;
 pub mod resources;
+pub mod setup;
 pub mod systems;
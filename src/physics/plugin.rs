@@ -0,0 +1,217 @@
+use crate::physics::{
+    apply_physics_changes_system, create_body_and_collider_system, create_joints_system,
+    destroy_body_and_collider_system, interpolate_transforms_system, move_characters_system,
+    step_world, sync_transforms_system, update_casters_system, PhysicsDeltaTime, PhysicsSetup,
+};
+
+use shipyard::error;
+use shipyard::{AllStoragesViewMut, IntoWorkloadSystem, UniqueView, WorkloadBuilder, World};
+use std::borrow::Cow;
+
+/// Configuration applied to the shipyard World when the physics workload is built.
+pub struct PhysicsPluginConfig {
+    /// The physics resources added to the World.
+    pub setup: PhysicsSetup,
+    /// Whether `sync_transforms_system` runs after the step.
+    pub sync_transforms: bool,
+    /// Whether `interpolate_transforms_system` runs after the step.
+    pub interpolate_transforms: bool,
+}
+
+impl Default for PhysicsPluginConfig {
+    fn default() -> Self {
+        Self {
+            setup: PhysicsSetup::new(),
+            sync_transforms: true,
+            interpolate_transforms: false,
+        }
+    }
+}
+
+/// Builder of a shipyard workload running the physics systems in order:
+///
+/// 1. `create_body_and_collider_system`
+/// 2. `create_joints_system`
+/// 3. the systems added with `before_step`
+/// 4. `move_characters_system`
+/// 5. `apply_physics_changes_system`, pushing the changes made by the systems above
+/// 6. `step_world_with_entity_hooks_system`, stepped by the `PhysicsDeltaTime` resource
+/// 7. `sync_transforms_system` and `interpolate_transforms_system`, if enabled
+/// 8. `update_casters_system`
+/// 9. the systems added with `after_step`
+/// 10. `destroy_body_and_collider_system`
+///
+/// ```ignore
+/// PhysicsPlugin::new(PhysicsPluginConfig::default())
+///     .before_step(player_input_system)
+///     .build(&world)
+///     .unwrap();
+///
+/// loop {
+///     world.borrow::<UniqueViewMut<PhysicsDeltaTime>>().unwrap().0 = get_frame_time();
+///     world.run_workload(PhysicsPlugin::WORKLOAD_NAME).unwrap();
+/// }
+/// ```
+pub struct PhysicsPlugin {
+    name: Cow<'static, str>,
+    config: PhysicsPluginConfig,
+    before_step: WorkloadBuilder,
+    after_step: WorkloadBuilder,
+}
+
+impl PhysicsPlugin {
+    /// Name of the workload, unless changed with `workload_name`.
+    pub const WORKLOAD_NAME: &'static str = "rapier_physics";
+
+    /// Creates a plugin that will setup the physics with the given configuration.
+    pub fn new(config: PhysicsPluginConfig) -> Self {
+        Self {
+            name: Cow::Borrowed(Self::WORKLOAD_NAME),
+            config,
+            before_step: WorkloadBuilder::default(),
+            after_step: WorkloadBuilder::default(),
+        }
+    }
+
+    /// Sets the name of the workload added to the World.
+    pub fn workload_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Adds a system running right before the physics step, like the systems setting
+    /// the `CharacterController`s translation.
+    pub fn before_step<B, R, S: IntoWorkloadSystem<B, R>>(mut self, system: S) -> Self {
+        self.before_step.with_system(system);
+        self
+    }
+
+    /// Adds a system running right after the physics step, the transform
+    /// synchronization and the casters update, but before the removal of deleted physics objects.
+    pub fn after_step<B, R, S: IntoWorkloadSystem<B, R>>(mut self, system: S) -> Self {
+        self.after_step.with_system(system);
+        self
+    }
+
+    /// Setups the physics resources in the World and adds the physics workload to it.
+    pub fn build(self, world: &World) -> Result<(), error::AddWorkload> {
+        let PhysicsPlugin {
+            name,
+            config,
+            mut before_step,
+            mut after_step,
+        } = self;
+        config.setup.install(world);
+
+        let mut workload = WorkloadBuilder::new(name);
+        workload
+            .with_system(create_body_and_collider_system)
+            .with_system(create_joints_system)
+            .append(&mut before_step)
+            .with_system(move_characters_system)
+            .with_system(apply_physics_changes_system)
+            .with_try_system(step_world_workload_system);
+        if config.sync_transforms {
+            workload.with_system(sync_transforms_system);
+        }
+        if config.interpolate_transforms {
+            workload.with_system(interpolate_transforms_system);
+        }
+        workload
+            .with_system(update_casters_system)
+            .append(&mut after_step)
+            .with_system(destroy_body_and_collider_system)
+            .add_to_world(world)
+            .map(|_| ())
+    }
+}
+
+impl Default for PhysicsPlugin {
+    fn default() -> Self {
+        Self::new(PhysicsPluginConfig::default())
+    }
+}
+
+/// Runs `step_world_with_entity_hooks_system` with the time stored in `PhysicsDeltaTime`.
+fn step_world_workload_system(all_storages: AllStoragesViewMut) -> Result<(), error::Run> {
+    let delta_seconds = all_storages.borrow::<UniqueView<PhysicsDeltaTime>>()?.0;
+    step_world(delta_seconds, &all_storages)
+}
+
+#[test]
+fn test_physics_plugin_before_step_changes() {
+    use crate::physics::{PhysicsTransform, Velocity};
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::ColliderBuilder;
+    use rapier::math::Vector;
+    use shipyard::{Get, IntoIter, UniqueViewMut, View, ViewMut};
+
+    fn push_right(mut velocities: ViewMut<Velocity>) {
+        for mut velocity in (&mut velocities).iter() {
+            velocity.linvel = Vector::x() * 10.0;
+        }
+    }
+
+    let mut world = World::new();
+    let mut config = PhysicsPluginConfig::default();
+    config.setup = config.setup.gravity(Vector::zeros());
+    PhysicsPlugin::new(config)
+        .before_step(push_right)
+        .build(&world)
+        .unwrap();
+
+    let entity = world.add_entity((
+        RigidBodyBuilder::new_dynamic(),
+        ColliderBuilder::ball(0.5),
+        Velocity::default(),
+    ));
+    world.borrow::<UniqueViewMut<PhysicsDeltaTime>>().unwrap().0 = 1.0 / 60.0;
+    world.run_workload(PhysicsPlugin::WORKLOAD_NAME).unwrap();
+
+    // the velocity set before the step moves the body during that same frame
+    let transforms = world.borrow::<View<PhysicsTransform>>().unwrap();
+    assert!(transforms.get(entity).unwrap().translation().x > 0.0);
+}
+
+#[test]
+fn test_physics_plugin() {
+    use crate::physics::PhysicsTransform;
+    use rapier::dynamics::{RigidBodyBuilder, RigidBodySet};
+    use rapier::geometry::ColliderBuilder;
+    use shipyard::{Get, IntoIter, UniqueViewMut, View};
+
+    struct StepLog(Vec<&'static str>);
+
+    fn before_step(mut log: UniqueViewMut<StepLog>, bodies: UniqueView<RigidBodySet>) {
+        assert_eq!(bodies.len(), 1);
+        log.0.push("before");
+    }
+
+    fn after_step(mut log: UniqueViewMut<StepLog>, transforms: View<PhysicsTransform>) {
+        let transform = transforms.iter().next().unwrap();
+        assert!(transform.translation().y < 0.0);
+        log.0.push("after");
+    }
+
+    let mut world = World::new();
+    world.add_unique(StepLog(Vec::new())).unwrap();
+    PhysicsPlugin::default()
+        .before_step(before_step)
+        .after_step(after_step)
+        .build(&world)
+        .unwrap();
+
+    let entity = world.add_entity((RigidBodyBuilder::new_dynamic(), ColliderBuilder::ball(0.5)));
+    world.borrow::<UniqueViewMut<PhysicsDeltaTime>>().unwrap().0 = 1.0 / 60.0;
+    world.run_workload(PhysicsPlugin::WORKLOAD_NAME).unwrap();
+
+    assert_eq!(
+        world.borrow::<UniqueView<StepLog>>().unwrap().0,
+        vec!["before", "after"]
+    );
+    assert!(world
+        .borrow::<View<PhysicsTransform>>()
+        .unwrap()
+        .get(entity)
+        .is_ok());
+}
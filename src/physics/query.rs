@@ -0,0 +1,869 @@
+use crate::physics::{
+    ColliderHandleComponent, ColliderParent, CollisionLayers, EntityMaps, RapierConfiguration,
+    RayCaster, RayHit, RayHits, RigidBodyHandleComponent, ShapeCaster, ShapeHits,
+};
+
+use crate::rapier::pipeline::QueryPipeline;
+use rapier::dynamics::RigidBodySet;
+use rapier::geometry::{
+    ColliderSet, InteractionGroups, PointProjection, Ray, RayIntersection, Shape, SharedShape, TOI,
+};
+use rapier::math::{Isometry, Point, Translation, Vector};
+use rapier::parry::bounding_volume::BoundingVolume;
+use rapier::parry::query;
+#[cfg(feature = "dim2")]
+use rapier::parry::shape::ConvexPolygon;
+#[cfg(feature = "dim3")]
+use rapier::parry::shape::{Cone, ConvexPolyhedron, Cylinder};
+use rapier::parry::shape::{Cuboid, HalfSpace, RoundShape, Segment, ShapeType, Triangle};
+
+use shipyard::borrow::{AllStoragesBorrow, Borrow, BorrowInfo, IntoBorrow};
+use shipyard::info::TypeInfo;
+use shipyard::{
+    error, AllStorages, EntitiesView, EntityId, Get, IntoIter, IntoWithId, UniqueView, View,
+    ViewMut, World,
+};
+use std::cmp::Ordering;
+
+type PhysicsQueryViews<'a> = (
+    UniqueView<'a, RapierConfiguration>,
+    UniqueView<'a, QueryPipeline>,
+    UniqueView<'a, ColliderSet>,
+    UniqueView<'a, EntityMaps>,
+);
+
+/// Scene queries over the `QueryPipeline`, usable as a system argument.
+///
+/// Every length given or returned, positions, distances, shapes, contact points and
+/// velocities, is in world units, that is Rapier units multiplied by
+/// `RapierConfiguration::scale`. Times of impact are times, not affected by the scale,
+/// except for rays, whose times of impact are distances along their direction.
+///
+/// Only the colliders whose collision groups interact with `groups` are considered, and
+/// the results are the entities owning the colliders. `groups` can be the
+/// `CollisionLayers` of an entity, to find the colliders it would interact with.
+///
+/// The `QueryPipeline` is updated by `step_world_system`, colliders created since
+/// the last step are not found.
+pub struct PhysicsQuery<'a> {
+    configuration: UniqueView<'a, RapierConfiguration>,
+    query_pipeline: UniqueView<'a, QueryPipeline>,
+    colliders: UniqueView<'a, ColliderSet>,
+    entity_maps: UniqueView<'a, EntityMaps>,
+}
+
+impl PhysicsQuery<'_> {
+    /// Finds the closest entity hit by a ray, and the time of impact such that the hit
+    /// point is `ray.point_at(toi)`.
+    pub fn cast_ray(
+        &self,
+        ray: &Ray,
+        max_toi: f32,
+        solid: bool,
+        groups: impl Into<InteractionGroups>,
+    ) -> Option<(EntityId, f32)> {
+        let scale = self.configuration.scale;
+        let (handle, toi) = self.query_pipeline.cast_ray(
+            &self.colliders,
+            &self.ray_to_physics(ray),
+            max_toi / scale,
+            solid,
+            groups.into(),
+        )?;
+        Some((self.entity_maps.collider_entity(handle)?, toi * scale))
+    }
+
+    /// Finds the closest entity hit by a ray, along with the time of impact and the
+    /// normal at the hit point.
+    pub fn cast_ray_and_get_normal(
+        &self,
+        ray: &Ray,
+        max_toi: f32,
+        solid: bool,
+        groups: impl Into<InteractionGroups>,
+    ) -> Option<(EntityId, RayIntersection)> {
+        let scale = self.configuration.scale;
+        let (handle, mut intersection) = self.query_pipeline.cast_ray_and_get_normal(
+            &self.colliders,
+            &self.ray_to_physics(ray),
+            max_toi / scale,
+            solid,
+            groups.into(),
+        )?;
+        intersection.toi *= scale;
+        Some((self.entity_maps.collider_entity(handle)?, intersection))
+    }
+
+    /// Calls `callback` with every entity hit by a ray and the intersection, in no
+    /// particular order, until it returns `false`.
+    pub fn intersections_with_ray(
+        &self,
+        ray: &Ray,
+        max_toi: f32,
+        solid: bool,
+        groups: impl Into<InteractionGroups>,
+        mut callback: impl FnMut(EntityId, RayIntersection) -> bool,
+    ) {
+        let scale = self.configuration.scale;
+        self.query_pipeline.intersections_with_ray(
+            &self.colliders,
+            &self.ray_to_physics(ray),
+            max_toi / scale,
+            solid,
+            groups.into(),
+            |handle, _, mut intersection| match self.entity_maps.collider_entity(handle) {
+                Some(entity) => {
+                    intersection.toi *= scale;
+                    callback(entity, intersection)
+                }
+                None => true,
+            },
+        );
+    }
+
+    /// Finds the first entity hit by a shape moving at a constant velocity, up to the time
+    /// `max_toi`.
+    ///
+    /// The witness points of the returned `TOI` are local to the shapes. Returns `None` if
+    /// the shape cannot be converted to Rapier units, see `intersections_with_shape`.
+    pub fn cast_shape(
+        &self,
+        shape_pos: &Isometry<f32>,
+        shape_vel: &Vector<f32>,
+        shape: &dyn Shape,
+        max_toi: f32,
+        groups: impl Into<InteractionGroups>,
+    ) -> Option<(EntityId, TOI)> {
+        let scale = self.configuration.scale;
+        let (handle, mut toi) = self.with_physics_shape(shape, |shape| {
+            self.query_pipeline.cast_shape(
+                &self.colliders,
+                &self.position_to_physics(shape_pos),
+                &(shape_vel / scale),
+                shape,
+                max_toi,
+                0.0,
+                groups.into(),
+            )
+        })??;
+        toi.witness1 *= scale;
+        toi.witness2 *= scale;
+        Some((self.entity_maps.collider_entity(handle)?, toi))
+    }
+
+    /// Calls `callback` with every entity intersecting the shape, until it returns `false`.
+    ///
+    /// When `RapierConfiguration::scale` is not 1, the shape is scaled to Rapier units. User
+    /// defined shapes, and convex shapes whose hull cannot be computed, cannot be scaled: the
+    /// query is not run and `None` is returned.
+    pub fn intersections_with_shape(
+        &self,
+        shape_pos: &Isometry<f32>,
+        shape: &dyn Shape,
+        groups: impl Into<InteractionGroups>,
+        mut callback: impl FnMut(EntityId) -> bool,
+    ) -> Option<()> {
+        self.with_physics_shape(shape, |shape| {
+            self.query_pipeline.intersections_with_shape(
+                &self.colliders,
+                &self.position_to_physics(shape_pos),
+                shape,
+                groups.into(),
+                |handle, _| match self.entity_maps.collider_entity(handle) {
+                    Some(entity) => callback(entity),
+                    None => true,
+                },
+            )
+        })
+    }
+
+    /// Calls `callback` with every entity containing the point, until it returns `false`.
+    pub fn intersections_with_point(
+        &self,
+        point: &Point<f32>,
+        groups: impl Into<InteractionGroups>,
+        mut callback: impl FnMut(EntityId) -> bool,
+    ) {
+        self.query_pipeline.intersections_with_point(
+            &self.colliders,
+            &(point / self.configuration.scale),
+            groups.into(),
+            |handle, _| match self.entity_maps.collider_entity(handle) {
+                Some(entity) => callback(entity),
+                None => true,
+            },
+        );
+    }
+
+    /// Finds the entity closest to a point, and the projection of the point on it.
+    pub fn project_point(
+        &self,
+        point: &Point<f32>,
+        solid: bool,
+        groups: impl Into<InteractionGroups>,
+    ) -> Option<(EntityId, PointProjection)> {
+        let scale = self.configuration.scale;
+        let (handle, mut projection) = self.query_pipeline.project_point(
+            &self.colliders,
+            &(point / scale),
+            solid,
+            groups.into(),
+        )?;
+        projection.point *= scale;
+        Some((self.entity_maps.collider_entity(handle)?, projection))
+    }
+
+    /// Finds every collider hit by a shape moving along the unit vector `direction`.
+    ///
+    /// Unlike the other queries, the shape is in Rapier units, like the colliders. Sensors
+    /// are skipped, they do not block the shape.
+    ///
+    /// `cast_shape` only finds the first collider: the candidates are the colliders
+    /// intersecting the AABB swept by the shape, then cast against one by one.
+    pub(crate) fn shape_hits(
+        &self,
+        shape_pos: &Isometry<f32>,
+        direction: &Vector<f32>,
+        shape: &dyn Shape,
+        max_distance: f32,
+        groups: InteractionGroups,
+        mut callback: impl FnMut(RayHit),
+    ) {
+        let scale = self.configuration.scale;
+        let start = self.position_to_physics(shape_pos);
+        let max_toi = max_distance / scale;
+        let mut end = start;
+        end.translation.vector += direction * max_toi;
+        let swept_aabb = shape.compute_aabb(&start).merged(&shape.compute_aabb(&end));
+
+        self.query_pipeline.intersections_with_shape(
+            &self.colliders,
+            &Translation::from(swept_aabb.center().coords).into(),
+            &Cuboid::new(swept_aabb.half_extents()),
+            groups,
+            |handle, collider| {
+                if collider.is_sensor() {
+                    return true;
+                }
+                let entity = match self.entity_maps.collider_entity(handle) {
+                    Some(entity) => entity,
+                    None => return true,
+                };
+                let toi = query::time_of_impact(
+                    &start,
+                    direction,
+                    shape,
+                    collider.position(),
+                    &Vector::zeros(),
+                    collider.shape(),
+                    max_toi,
+                    0.0,
+                );
+                if let Ok(Some(toi)) = toi {
+                    callback(RayHit {
+                        entity,
+                        point: collider.position() * toi.witness2 * scale,
+                        normal: collider.position() * toi.normal2.into_inner(),
+                        toi: toi.toi * scale,
+                    });
+                }
+                true
+            },
+        );
+    }
+
+    pub(crate) fn scale(&self) -> f32 {
+        self.configuration.scale
+    }
+
+    pub(crate) fn colliders(&self) -> &ColliderSet {
+        &self.colliders
+    }
+
+    fn ray_to_physics(&self, ray: &Ray) -> Ray {
+        Ray::new(ray.origin / self.configuration.scale, ray.dir)
+    }
+
+    /// Calls `f` with the shape in Rapier units, `None` if it cannot be scaled.
+    pub(crate) fn with_physics_shape<R>(
+        &self,
+        shape: &dyn Shape,
+        f: impl FnOnce(&dyn Shape) -> R,
+    ) -> Option<R> {
+        let scale = self.configuration.scale;
+        if scale == 1.0 {
+            Some(f(shape))
+        } else {
+            Some(f(&*scaled_shape(shape, 1.0 / scale)?))
+        }
+    }
+
+    fn position_to_physics(&self, position: &Isometry<f32>) -> Isometry<f32> {
+        let mut position = *position;
+        position.translation.vector /= self.configuration.scale;
+        position
+    }
+}
+
+/// Scales a shape by `factor`. User-defined shapes, and convex shapes whose hull cannot be
+/// computed, cannot be scaled.
+pub(crate) fn scaled_shape(shape: &dyn Shape, factor: f32) -> Option<SharedShape> {
+    let points = |points: &[Point<f32>]| -> Vec<_> { points.iter().map(|p| p * factor).collect() };
+    let triangle = |t: &Triangle| Triangle::new(t.a * factor, t.b * factor, t.c * factor);
+
+    let scaled = match shape.shape_type() {
+        ShapeType::Ball => SharedShape::ball(shape.as_ball()?.radius * factor),
+        ShapeType::Cuboid => {
+            SharedShape::new(Cuboid::new(shape.as_cuboid()?.half_extents * factor))
+        }
+        ShapeType::Capsule => {
+            let c = shape.as_capsule()?;
+            SharedShape::capsule(
+                c.segment.a * factor,
+                c.segment.b * factor,
+                c.radius * factor,
+            )
+        }
+        ShapeType::Segment => {
+            let s = shape.as_shape::<Segment>()?;
+            SharedShape::segment(s.a * factor, s.b * factor)
+        }
+        ShapeType::Triangle => SharedShape::new(triangle(shape.as_triangle()?)),
+        ShapeType::TriMesh => {
+            let t = shape.as_trimesh()?;
+            SharedShape::trimesh(points(t.vertices()), t.indices().to_vec())
+        }
+        ShapeType::Polyline => {
+            let p = shape.as_polyline()?;
+            SharedShape::polyline(points(p.vertices()), Some(p.indices().to_vec()))
+        }
+        ShapeType::HalfSpace => SharedShape::new(shape.as_shape::<HalfSpace>()?.clone()),
+        ShapeType::HeightField => {
+            let h = shape.as_heightfield()?;
+            SharedShape::heightfield(h.heights().clone(), h.scale() * factor)
+        }
+        ShapeType::Compound => {
+            let shapes = shape.as_compound()?.shapes().iter().map(|(pos, shape)| {
+                let mut pos = *pos;
+                pos.translation.vector *= factor;
+                Some((pos, scaled_shape(&**shape, factor)?))
+            });
+            SharedShape::compound(shapes.collect::<Option<_>>()?)
+        }
+        ShapeType::RoundCuboid => {
+            let c = shape.as_round_cuboid()?;
+            let base_shape = Cuboid::new(c.base_shape.half_extents * factor);
+            SharedShape::new(RoundShape {
+                base_shape,
+                border_radius: c.border_radius * factor,
+            })
+        }
+        ShapeType::RoundTriangle => {
+            let t = shape.as_round_triangle()?;
+            SharedShape::new(RoundShape {
+                base_shape: triangle(&t.base_shape),
+                border_radius: t.border_radius * factor,
+            })
+        }
+        #[cfg(feature = "dim2")]
+        ShapeType::ConvexPolygon => {
+            let p = shape.as_convex_polygon()?;
+            SharedShape::new(ConvexPolygon::from_convex_hull(&points(p.points()))?)
+        }
+        #[cfg(feature = "dim2")]
+        ShapeType::RoundConvexPolygon => {
+            let p = shape.as_round_convex_polygon()?;
+            let base_shape = ConvexPolygon::from_convex_hull(&points(p.base_shape.points()))?;
+            SharedShape::new(RoundShape {
+                base_shape,
+                border_radius: p.border_radius * factor,
+            })
+        }
+        #[cfg(feature = "dim3")]
+        ShapeType::ConvexPolyhedron => {
+            let p = shape.as_convex_polyhedron()?;
+            SharedShape::new(ConvexPolyhedron::from_convex_hull(&points(p.points()))?)
+        }
+        #[cfg(feature = "dim3")]
+        ShapeType::RoundConvexPolyhedron => {
+            let p = shape.as_round_convex_polyhedron()?;
+            let base_shape = ConvexPolyhedron::from_convex_hull(&points(p.base_shape.points()))?;
+            SharedShape::new(RoundShape {
+                base_shape,
+                border_radius: p.border_radius * factor,
+            })
+        }
+        #[cfg(feature = "dim3")]
+        ShapeType::Cylinder => {
+            let c = shape.as_cylinder()?;
+            SharedShape::cylinder(c.half_height * factor, c.radius * factor)
+        }
+        #[cfg(feature = "dim3")]
+        ShapeType::Cone => {
+            let c = shape.as_cone()?;
+            SharedShape::cone(c.half_height * factor, c.radius * factor)
+        }
+        #[cfg(feature = "dim3")]
+        ShapeType::RoundCylinder => {
+            let c = shape.as_round_cylinder()?;
+            let base_shape = Cylinder::new(
+                c.base_shape.half_height * factor,
+                c.base_shape.radius * factor,
+            );
+            SharedShape::new(RoundShape {
+                base_shape,
+                border_radius: c.border_radius * factor,
+            })
+        }
+        #[cfg(feature = "dim3")]
+        ShapeType::RoundCone => {
+            let c = shape.as_round_cone()?;
+            let base_shape = Cone::new(
+                c.base_shape.half_height * factor,
+                c.base_shape.radius * factor,
+            );
+            SharedShape::new(RoundShape {
+                base_shape,
+                border_radius: c.border_radius * factor,
+            })
+        }
+        ShapeType::Custom => return None,
+    };
+    Some(scaled)
+}
+
+/// Borrows the storages of a `PhysicsQuery`.
+pub struct PhysicsQueryBorrower;
+
+impl IntoBorrow for PhysicsQuery<'_> {
+    type Borrow = PhysicsQueryBorrower;
+}
+
+impl<'a> Borrow<'a> for PhysicsQueryBorrower {
+    type View = PhysicsQuery<'a>;
+
+    fn borrow(world: &'a World) -> Result<Self::View, error::GetStorage> {
+        let (configuration, query_pipeline, colliders, entity_maps) =
+            <PhysicsQueryViews<'a> as IntoBorrow>::Borrow::borrow(world)?;
+        Ok(PhysicsQuery {
+            configuration,
+            query_pipeline,
+            colliders,
+            entity_maps,
+        })
+    }
+}
+
+impl<'a> AllStoragesBorrow<'a> for PhysicsQueryBorrower {
+    fn all_borrow(all_storages: &'a AllStorages) -> Result<Self::View, error::GetStorage> {
+        let (configuration, query_pipeline, colliders, entity_maps) =
+            <PhysicsQueryViews<'a> as IntoBorrow>::Borrow::all_borrow(all_storages)?;
+        Ok(PhysicsQuery {
+            configuration,
+            query_pipeline,
+            colliders,
+            entity_maps,
+        })
+    }
+}
+
+unsafe impl BorrowInfo for PhysicsQuery<'_> {
+    fn borrow_info(info: &mut Vec<TypeInfo>) {
+        PhysicsQueryViews::borrow_info(info);
+    }
+}
+
+/// System responsible for casting the rays of the `RayCaster`s and the shapes of the
+/// `ShapeCaster`s, and writing their hits to the `RayHits` and `ShapeHits` of their entities.
+///
+/// The casters are positioned from the `RigidBodySet` and `ColliderSet`, so the hits are up
+/// to date even when the `PhysicsTransform`s are not synchronized. This should run after
+/// `step_world_system`.
+#[allow(clippy::too_many_arguments)]
+pub fn update_casters_system(
+    entities: EntitiesView,
+    query: PhysicsQuery,
+    (bodies, body_handles, collider_handles): (
+        UniqueView<RigidBodySet>,
+        View<RigidBodyHandleComponent>,
+        View<ColliderHandleComponent>,
+    ),
+    ray_casters: View<RayCaster>,
+    shape_casters: View<ShapeCaster>,
+    (collider_parents, layers): (View<ColliderParent>, View<CollisionLayers>),
+    mut ray_hits: ViewMut<RayHits>,
+    mut shape_hits: ViewMut<ShapeHits>,
+) {
+    let caster_position = |entity| {
+        let body_position = body_handles
+            .get(entity)
+            .ok()
+            .and_then(|handle| bodies.get(handle.handle()))
+            .map(|body| body.position());
+        let collider_position = || {
+            collider_handles
+                .get(entity)
+                .ok()
+                .and_then(|handle| query.colliders().get(handle.handle()))
+                .map(|collider| collider.position())
+        };
+        let mut position = body_position
+            .or_else(collider_position)
+            .copied()
+            .unwrap_or_else(Isometry::identity);
+        position.translation.vector *= query.scale();
+        position
+    };
+
+    for (entity, caster) in ray_casters.iter().with_id() {
+        let position = caster_position(entity);
+        let direction = match (position.rotation * caster.direction).try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => continue,
+        };
+        let ray = Ray::new(position * caster.origin, direction);
+
+        let mut hits = Vec::new();
+        query.intersections_with_ray(
+            &ray,
+            caster.max_distance,
+            caster.solid,
+            layer_groups(&layers, entity, caster.groups),
+            |hit_entity, intersection| {
+                if !is_own_collider(&collider_parents, entity, hit_entity) {
+                    hits.push(RayHit {
+                        entity: hit_entity,
+                        point: ray.point_at(intersection.toi),
+                        normal: intersection.normal,
+                        toi: intersection.toi,
+                    });
+                }
+                true
+            },
+        );
+        let hits = sorted_hits(hits, caster.max_hits);
+        entities.add_component(entity, &mut ray_hits, RayHits(hits));
+    }
+
+    for (entity, caster) in shape_casters.iter().with_id() {
+        let position = caster_position(entity);
+        let direction = match (position.rotation * caster.direction).try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => continue,
+        };
+
+        let mut hits = Vec::new();
+        query.with_physics_shape(&*caster.shape, |shape| {
+            query.shape_hits(
+                &(position * caster.origin),
+                &direction,
+                shape,
+                caster.max_distance,
+                layer_groups(&layers, entity, caster.groups),
+                |hit| {
+                    if !is_own_collider(&collider_parents, entity, hit.entity) {
+                        hits.push(hit);
+                    }
+                },
+            )
+        });
+        let hits = sorted_hits(hits, caster.max_hits);
+        entities.add_component(entity, &mut shape_hits, ShapeHits(hits));
+    }
+}
+
+/// Whether the collider of `entity` belongs to `owner`, directly or through a `ColliderParent`.
+pub(crate) fn is_own_collider(
+    collider_parents: &View<ColliderParent>,
+    owner: EntityId,
+    entity: EntityId,
+) -> bool {
+    entity == owner
+        || matches!(collider_parents.get(entity), Ok(ColliderParent(parent)) if *parent == owner)
+}
+
+/// Restricts both the memberships and the filter of `groups` to the `CollisionLayers` of
+/// `entity`, if it has some.
+pub(crate) fn layer_groups(
+    layers: &View<CollisionLayers>,
+    entity: EntityId,
+    groups: InteractionGroups,
+) -> InteractionGroups {
+    match layers.get(entity) {
+        Ok(layers) => InteractionGroups(groups.0 & layers.collision_groups().0),
+        Err(_) => groups,
+    }
+}
+
+fn sorted_hits(mut hits: Vec<RayHit>, max_hits: usize) -> Vec<RayHit> {
+    hits.sort_by(|hit1, hit2| hit1.toi.partial_cmp(&hit2.toi).unwrap_or(Ordering::Equal));
+    hits.truncate(max_hits);
+    hits
+}
+
+#[test]
+fn test_physics_query() {
+    use crate::physics::{
+        create_body_and_collider_system, step_world_system, CollisionLayers, PhysicsSetup,
+    };
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::{Ball, ColliderBuilder};
+    use rapier::math::Translation;
+    use rapier::parry::bounding_volume::AABB;
+    use rapier::parry::mass_properties::MassProperties;
+    use rapier::parry::query::{PointQuery, RayCast};
+    use rapier::parry::shape::{FeatureId, SupportMap, TypedShape};
+
+    // a user-defined shape, which cannot be scaled to Rapier units
+    struct CustomBall(Ball);
+
+    impl RayCast for CustomBall {
+        fn cast_local_ray_and_get_normal(
+            &self,
+            ray: &Ray,
+            max_toi: f32,
+            solid: bool,
+        ) -> Option<RayIntersection> {
+            self.0.cast_local_ray_and_get_normal(ray, max_toi, solid)
+        }
+    }
+
+    impl PointQuery for CustomBall {
+        fn project_local_point(&self, point: &Point<f32>, solid: bool) -> PointProjection {
+            self.0.project_local_point(point, solid)
+        }
+
+        fn project_local_point_and_get_feature(
+            &self,
+            point: &Point<f32>,
+        ) -> (PointProjection, FeatureId) {
+            self.0.project_local_point_and_get_feature(point)
+        }
+    }
+
+    impl Shape for CustomBall {
+        fn compute_local_aabb(&self) -> AABB {
+            self.0.compute_local_aabb()
+        }
+
+        fn mass_properties(&self, density: f32) -> MassProperties {
+            self.0.mass_properties(density)
+        }
+
+        fn shape_type(&self) -> ShapeType {
+            ShapeType::Custom
+        }
+
+        fn as_typed_shape(&self) -> TypedShape<'_> {
+            TypedShape::Custom(0)
+        }
+
+        fn ccd_thickness(&self) -> f32 {
+            self.0.ccd_thickness()
+        }
+
+        fn as_support_map(&self) -> Option<&dyn SupportMap> {
+            self.0.as_support_map()
+        }
+    }
+
+    let mut world = World::new();
+    PhysicsSetup::new()
+        .gravity(Vector::zeros())
+        .scale(10.0)
+        .install(&world);
+
+    // a ball of radius 5 world units, 20 world units to the right
+    let ball = world.add_entity((
+        RigidBodyBuilder::new_static().position(Translation::from(Vector::x() * 2.0).into()),
+        ColliderBuilder::ball(0.5),
+    ));
+    world.run(create_body_and_collider_system).unwrap();
+    world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+
+    world
+        .run(|query: PhysicsQuery| {
+            let ray = Ray::new(Point::origin(), Vector::x());
+            let (entity, toi) = query
+                .cast_ray(&ray, 100.0, true, InteractionGroups::all())
+                .unwrap();
+            assert_eq!(entity, ball);
+            assert!((toi - 15.0).abs() < 1.0e-3);
+            assert!(query
+                .cast_ray(&ray, 10.0, true, InteractionGroups::all())
+                .is_none());
+            assert!(query
+                .cast_ray(&ray, 100.0, true, InteractionGroups::none())
+                .is_none());
+
+            let (entity, intersection) = query
+                .cast_ray_and_get_normal(&ray, 100.0, true, InteractionGroups::all())
+                .unwrap();
+            assert_eq!(entity, ball);
+            assert!((intersection.normal + Vector::x()).norm() < 1.0e-3);
+
+            let (entity, toi) = query
+                .cast_shape(
+                    &Isometry::identity(),
+                    &(Vector::x() * 10.0),
+                    &Ball::new(5.0),
+                    10.0,
+                    InteractionGroups::all(),
+                )
+                .unwrap();
+            assert_eq!(entity, ball);
+            assert!((toi.toi - 1.0).abs() < 1.0e-3);
+            assert!((toi.witness2.coords.norm() - 5.0).abs() < 1.0e-3);
+            assert!(query
+                .cast_shape(
+                    &Isometry::identity(),
+                    &(Vector::x() * 10.0),
+                    &CustomBall(Ball::new(5.0)),
+                    10.0,
+                    InteractionGroups::all(),
+                )
+                .is_none());
+            assert!(query
+                .cast_ray(&ray, 100.0, true, CollisionLayers::none())
+                .is_none());
+
+            let mut hits = Vec::new();
+            let result = query.intersections_with_shape(
+                &Translation::from(Vector::x() * 17.0).into(),
+                &Ball::new(0.5),
+                InteractionGroups::all(),
+                |entity| {
+                    hits.push(entity);
+                    true
+                },
+            );
+            assert_eq!(result, Some(()));
+            assert_eq!(hits, vec![ball]);
+
+            let mut hits = Vec::new();
+            let result = query.intersections_with_shape(
+                &Translation::from(Vector::x() * 17.0).into(),
+                &CustomBall(Ball::new(0.5)),
+                InteractionGroups::all(),
+                |entity| {
+                    hits.push(entity);
+                    true
+                },
+            );
+            assert_eq!(result, None);
+            assert!(hits.is_empty());
+
+            let mut hits = Vec::new();
+            query.intersections_with_point(
+                &Point::from(Vector::x() * 22.0),
+                InteractionGroups::all(),
+                |entity| {
+                    hits.push(entity);
+                    true
+                },
+            );
+            assert_eq!(hits, vec![ball]);
+
+            let (entity, projection) = query
+                .project_point(&Point::origin(), true, InteractionGroups::all())
+                .unwrap();
+            assert_eq!(entity, ball);
+            assert!((projection.point.x - 15.0).abs() < 1.0e-3);
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_update_casters_system() {
+    use crate::physics::{create_body_and_collider_system, step_world_system, PhysicsSetup};
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::{ColliderBuilder, SharedShape};
+    use shipyard::UniqueViewMut;
+
+    let mut world = World::new();
+    PhysicsSetup::new()
+        .gravity(Vector::zeros())
+        .scale(10.0)
+        .install(&world);
+
+    // two walls at 20 and 40 world units, the casters do not hit their own colliders
+    let wall1 = world.add_entity((
+        RigidBodyBuilder::new_static().position(Translation::from(Vector::x() * 2.0).into()),
+        ColliderBuilder::ball(0.5),
+    ));
+    let wall2 = world.add_entity((
+        RigidBodyBuilder::new_static().position(Translation::from(Vector::x() * 4.0).into()),
+        ColliderBuilder::ball(0.5),
+        CollisionLayers::new(0b10, u16::MAX),
+    ));
+    let ray_caster = world.add_entity((
+        RigidBodyBuilder::new_static(),
+        RayCaster::new(Vector::x(), 100.0).with_max_hits(2),
+    ));
+    let ray_caster_collider =
+        world.add_entity((ColliderBuilder::ball(0.5), ColliderParent(ray_caster)));
+    let shape_caster = world.add_entity((
+        RigidBodyBuilder::new_static().position(Translation::from(Vector::x() * -2.0).into()),
+        ColliderBuilder::ball(0.5),
+        ShapeCaster::new(SharedShape::ball(5.0), Vector::x(), 100.0),
+        RayCaster::new(Vector::x(), 100.0),
+    ));
+    let short_caster = world.add_entity((
+        RayCaster::new(Vector::x(), 4.0).with_origin(Point::from(Vector::x() * 10.0)),
+    ));
+    // the layers of the caster hide the second wall
+    let layered_caster = world.add_entity((
+        RayCaster::new(Vector::x(), 100.0)
+            .with_origin(Point::from(Vector::x() * 10.0))
+            .with_max_hits(2),
+        CollisionLayers::new(0b01, 0b01),
+    ));
+    world.run(create_body_and_collider_system).unwrap();
+    world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+
+    // the shape caster is moved back 10 world units, its transform is not synchronized
+    world
+        .run(
+            |mut bodies: UniqueViewMut<RigidBodySet>,
+             body_handles: View<RigidBodyHandleComponent>| {
+                let handle = body_handles.get(shape_caster).unwrap().handle();
+                let position = Translation::from(Vector::x() * -3.0).into();
+                bodies
+                    .get_mut(handle)
+                    .unwrap()
+                    .set_position(position, false);
+            },
+        )
+        .unwrap();
+    world.run(update_casters_system).unwrap();
+
+    let ray_hits = world.borrow::<View<RayHits>>().unwrap();
+    let hits = &ray_hits.get(ray_caster).unwrap().0;
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].entity, wall1);
+    assert!((hits[0].toi - 15.0).abs() < 1.0e-3);
+    assert!((hits[0].point.x - 15.0).abs() < 1.0e-3);
+    assert!((hits[0].normal + Vector::x()).norm() < 1.0e-3);
+    assert_eq!(hits[1].entity, wall2);
+    assert!((hits[1].toi - 35.0).abs() < 1.0e-3);
+
+    let shape_hits = world.borrow::<View<ShapeHits>>().unwrap();
+    let hits = &shape_hits.get(shape_caster).unwrap().0;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].entity, ray_caster_collider);
+    assert!((hits[0].toi - 20.0).abs() < 1.0e-3);
+    assert!((hits[0].point.x + 5.0).abs() < 1.0e-3);
+    assert!((hits[0].normal + Vector::x()).norm() < 1.0e-3);
+
+    // the ray hits of an entity with both casters are kept apart
+    let hits = &ray_hits.get(shape_caster).unwrap().0;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].entity, ray_caster_collider);
+    assert!((hits[0].toi - 25.0).abs() < 1.0e-3);
+
+    assert!(ray_hits.get(short_caster).unwrap().is_empty());
+
+    let hits = &ray_hits.get(layered_caster).unwrap().0;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].entity, wall1);
+}
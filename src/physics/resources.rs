@@ -1,9 +1,15 @@
+use crate::physics::hooks::BorrowPhysicsHooks;
+use crate::physics::EntityPhysicsHooks;
 use crate::rapier::{
-    geometry::{ContactEvent, IntersectionEvent},
+    dynamics::{JointHandle, JointSet, RigidBodyBuilder, RigidBodyHandle, RigidBodySet},
+    geometry::{ColliderHandle, ContactEvent, IntersectionEvent},
     pipeline::{EventHandler, PhysicsHooks},
 };
 use concurrent_queue::ConcurrentQueue;
 use rapier::math::Vector;
+use shipyard::{error, AllStorages, EntityId};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 
 /// A resource for specifying configuration information for the physics simulation
 pub struct RapierConfiguration {
@@ -20,6 +26,32 @@ pub struct RapierConfiguration {
     /// Specifies if the number of physics steps run at each frame should depend
     /// of the real-world time elapsed since the last step.
     pub time_dependent_number_of_timesteps: bool,
+    /// The maximum number of physics steps run in a single frame when
+    /// `time_dependent_number_of_timesteps` is enabled.
+    pub max_substeps: usize,
+    /// The maximum simulation time, in seconds, that can be accumulated while
+    /// waiting to be simulated when `time_dependent_number_of_timesteps` is enabled.
+    pub max_accumulated_time: f32,
+    /// What to do with the simulation time exceeding `max_substeps` or `max_accumulated_time`.
+    pub substep_overflow_policy: SubstepOverflowPolicy,
+    /// Specifies if the entities of joints broken by the deletion of one of their rigid-bodies
+    /// should be deleted, instead of only losing their `JointHandleComponent`.
+    pub delete_broken_joint_entities: bool,
+}
+
+/// What `step_world_system` does when more simulation time is accumulated than
+/// `RapierConfiguration::max_substeps` steps can simulate in a single frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubstepOverflowPolicy {
+    /// Discard the excess time: the simulation falls behind real-world time.
+    DropExcessTime,
+    /// Keep the excess time, up to `RapierConfiguration::max_accumulated_time`, to be
+    /// simulated during the next frames: the simulation runs in slow-motion until it
+    /// catches up with real-world time.
+    SlowMotion,
+    /// Ignore both limits and run every step, only reporting the overflow through
+    /// `SimulationToRenderTime::overflowed`.
+    Report,
 }
 
 impl Default for RapierConfiguration {
@@ -30,6 +62,10 @@ impl Default for RapierConfiguration {
             physics_pipeline_active: true,
             query_pipeline_active: true,
             time_dependent_number_of_timesteps: false,
+            max_substeps: 8,
+            max_accumulated_time: 0.25,
+            substep_overflow_policy: SubstepOverflowPolicy::DropExcessTime,
+            delete_broken_joint_entities: false,
         }
     }
 }
@@ -44,8 +80,15 @@ pub struct EventQueue {
     pub contact_events: ConcurrentQueue<ContactEvent>,
     /// The unbounded intersection event queue.
     pub intersection_events: ConcurrentQueue<IntersectionEvent>,
+    /// The unbounded queue of contact and intersection events resolved to the
+    /// entities owning the colliders involved.
+    pub collision_events: ConcurrentQueue<CollisionEvent>,
+    /// The unbounded queue of joints removed because one of their rigid-bodies was removed.
+    pub broken_joints: ConcurrentQueue<JointBrokenEvent>,
     /// Are these queues automatically cleared before each simulation timestep?
     pub auto_clear: bool,
+    /// Events of the current step still waiting to be resolved to entities.
+    unresolved_events: ConcurrentQueue<RawEvent>,
 }
 
 impl EventQueue {
@@ -54,24 +97,338 @@ impl EventQueue {
         Self {
             contact_events: ConcurrentQueue::unbounded(),
             intersection_events: ConcurrentQueue::unbounded(),
+            collision_events: ConcurrentQueue::unbounded(),
+            broken_joints: ConcurrentQueue::unbounded(),
             auto_clear,
+            unresolved_events: ConcurrentQueue::unbounded(),
         }
     }
 
     /// Removes all events contained by this queue.
     pub fn clear(&self) {
-        while let Ok(_) = self.contact_events.pop() {}
-        while let Ok(_) = self.intersection_events.pop() {}
+        while self.contact_events.pop().is_ok() {}
+        while self.intersection_events.pop().is_ok() {}
+        while self.collision_events.pop().is_ok() {}
+        while self.broken_joints.pop().is_ok() {}
+    }
+
+    /// Converts the events received since the last call into `CollisionEvent`s.
+    ///
+    /// Events involving a collider that is not owned by any entity are dropped.
+    pub(crate) fn resolve_entities(&self, entity_maps: &EntityMaps) {
+        while let Ok(event) = self.unresolved_events.pop() {
+            let (collider1, collider2) = match event {
+                RawEvent::Contact(ContactEvent::Started(h1, h2))
+                | RawEvent::Contact(ContactEvent::Stopped(h1, h2)) => (h1, h2),
+                RawEvent::Intersection(event) => (event.collider1, event.collider2),
+            };
+            let (entity1, entity2) = match (
+                entity_maps.collider_entity(collider1),
+                entity_maps.collider_entity(collider2),
+            ) {
+                (Some(entity1), Some(entity2)) => (entity1, entity2),
+                _ => continue,
+            };
+            let pair = CollisionPair {
+                entity1,
+                entity2,
+                collider1,
+                collider2,
+            };
+            let event = match event {
+                RawEvent::Contact(ContactEvent::Started(..)) => {
+                    CollisionEvent::CollisionStarted(pair)
+                }
+                RawEvent::Contact(ContactEvent::Stopped(..)) => {
+                    CollisionEvent::CollisionStopped(pair)
+                }
+                RawEvent::Intersection(event) if event.intersecting => {
+                    CollisionEvent::IntersectionStarted(pair)
+                }
+                RawEvent::Intersection(_) => CollisionEvent::IntersectionStopped(pair),
+            };
+            let _ = self.collision_events.push(event);
+        }
     }
 }
 
 impl EventHandler for EventQueue {
     fn handle_intersection_event(&self, event: IntersectionEvent) {
         let _ = self.intersection_events.push(event);
+        let _ = self.unresolved_events.push(RawEvent::Intersection(event));
     }
 
     fn handle_contact_event(&self, event: ContactEvent) {
         let _ = self.contact_events.push(event);
+        let _ = self.unresolved_events.push(RawEvent::Contact(event));
+    }
+}
+
+enum RawEvent {
+    Contact(ContactEvent),
+    Intersection(IntersectionEvent),
+}
+
+/// The two entities, and their colliders, involved in a `CollisionEvent`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CollisionPair {
+    /// The entity owning the first collider.
+    pub entity1: EntityId,
+    /// The entity owning the second collider.
+    pub entity2: EntityId,
+    /// The first collider involved.
+    pub collider1: ColliderHandle,
+    /// The second collider involved.
+    pub collider2: ColliderHandle,
+}
+
+/// A contact or intersection event expressed in terms of shipyard entities.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CollisionEvent {
+    /// Two non-sensor colliders started touching.
+    CollisionStarted(CollisionPair),
+    /// Two non-sensor colliders stopped touching.
+    CollisionStopped(CollisionPair),
+    /// A sensor collider started intersecting another collider.
+    IntersectionStarted(CollisionPair),
+    /// A sensor collider stopped intersecting another collider.
+    IntersectionStopped(CollisionPair),
+}
+
+impl CollisionEvent {
+    /// The entities and colliders involved in this event.
+    pub fn pair(&self) -> &CollisionPair {
+        match self {
+            CollisionEvent::CollisionStarted(pair)
+            | CollisionEvent::CollisionStopped(pair)
+            | CollisionEvent::IntersectionStarted(pair)
+            | CollisionEvent::IntersectionStopped(pair) => pair,
+        }
+    }
+}
+
+/// Event emitted by `destroy_body_and_collider_system` when a joint is removed because
+/// the rigid-body of one of the entities it attaches was removed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct JointBrokenEvent {
+    /// The entity that was holding the `JointHandleComponent`.
+    pub joint: EntityId,
+    /// The first entity attached by the joint.
+    pub entity1: EntityId,
+    /// The second entity attached by the joint.
+    pub entity2: EntityId,
+}
+
+struct HandleMap<H> {
+    entities: HashMap<H, EntityId>,
+    handles: HashMap<EntityId, H>,
+}
+
+impl<H: Copy + Eq + Hash> HandleMap<H> {
+    fn insert(&mut self, entity: EntityId, handle: H) {
+        if let Some(previous) = self.handles.insert(entity, handle) {
+            self.entities.remove(&previous);
+        }
+        self.entities.insert(handle, entity);
+    }
+
+    fn remove_handle(&mut self, handle: H) -> Option<EntityId> {
+        let entity = self.entities.remove(&handle)?;
+        if self.handles.get(&entity) == Some(&handle) {
+            self.handles.remove(&entity);
+        }
+        Some(entity)
+    }
+
+    fn retain_handles(&mut self, mut keep: impl FnMut(H) -> bool) {
+        let handles = &mut self.handles;
+        self.entities.retain(|handle, entity| {
+            if keep(*handle) {
+                return true;
+            }
+            if handles.get(entity) == Some(handle) {
+                handles.remove(entity);
+            }
+            false
+        });
+    }
+}
+
+impl<H> Default for HandleMap<H> {
+    fn default() -> Self {
+        Self {
+            entities: HashMap::new(),
+            handles: HashMap::new(),
+        }
+    }
+}
+
+/// A resource mapping the Rapier handles created by this plugin to the entities owning them.
+///
+/// It is filled by the `create_*` systems and pruned by `destroy_body_and_collider_system`,
+/// allowing handles found in query results, contact pairs or physics hooks to be
+/// translated back to entities.
+#[derive(Default)]
+pub struct EntityMaps {
+    bodies: HandleMap<RigidBodyHandle>,
+    colliders: HandleMap<ColliderHandle>,
+    joints: HandleMap<JointHandle>,
+}
+
+impl EntityMaps {
+    /// The entity owning the given rigid-body.
+    pub fn body_entity(&self, handle: RigidBodyHandle) -> Option<EntityId> {
+        self.bodies.entities.get(&handle).copied()
+    }
+
+    /// The entity owning the given collider.
+    pub fn collider_entity(&self, handle: ColliderHandle) -> Option<EntityId> {
+        self.colliders.entities.get(&handle).copied()
+    }
+
+    /// The entity owning the given joint.
+    pub fn joint_entity(&self, handle: JointHandle) -> Option<EntityId> {
+        self.joints.entities.get(&handle).copied()
+    }
+
+    /// The rigid-body owned by the given entity.
+    pub fn body_handle(&self, entity: EntityId) -> Option<RigidBodyHandle> {
+        self.bodies.handles.get(&entity).copied()
+    }
+
+    /// The collider owned by the given entity.
+    pub fn collider_handle(&self, entity: EntityId) -> Option<ColliderHandle> {
+        self.colliders.handles.get(&entity).copied()
+    }
+
+    /// The joint owned by the given entity.
+    pub fn joint_handle(&self, entity: EntityId) -> Option<JointHandle> {
+        self.joints.handles.get(&entity).copied()
+    }
+
+    pub(crate) fn insert_body(&mut self, entity: EntityId, handle: RigidBodyHandle) {
+        self.bodies.insert(entity, handle);
+    }
+
+    pub(crate) fn insert_collider(&mut self, entity: EntityId, handle: ColliderHandle) {
+        self.colliders.insert(entity, handle);
+    }
+
+    pub(crate) fn insert_joint(&mut self, entity: EntityId, handle: JointHandle) {
+        self.joints.insert(entity, handle);
+    }
+
+    pub(crate) fn remove_body(&mut self, handle: RigidBodyHandle) -> Option<EntityId> {
+        self.bodies.remove_handle(handle)
+    }
+
+    pub(crate) fn remove_collider(&mut self, handle: ColliderHandle) -> Option<EntityId> {
+        self.colliders.remove_handle(handle)
+    }
+
+    pub(crate) fn remove_joint(&mut self, handle: JointHandle) -> Option<EntityId> {
+        self.joints.remove_handle(handle)
+    }
+
+    /// Removes the joints no longer in `joints`, like those Rapier removes with a rigid-body.
+    pub(crate) fn retain_joints(&mut self, joints: &JointSet) {
+        self.joints
+            .retain_handles(|handle| joints.get(handle).is_some());
+    }
+}
+
+/// The static rigid-body that colliders created without a rigid-body of their own
+/// are attached to.
+///
+/// It is created by `create_body_and_collider_system` the first time such a collider is found.
+#[derive(Default)]
+pub struct StaticGroundBody(pub(crate) Option<RigidBodyHandle>);
+
+impl StaticGroundBody {
+    /// The handle of the ground rigid-body, if it was created.
+    pub fn handle(&self) -> Option<RigidBodyHandle> {
+        self.0
+    }
+
+    /// The handle of the ground rigid-body, inserting it in `bodies` if it was not created.
+    pub(crate) fn get_or_insert(&mut self, bodies: &mut RigidBodySet) -> RigidBodyHandle {
+        *self
+            .0
+            .get_or_insert_with(|| bodies.insert(RigidBodyBuilder::new_static().build()))
+    }
+}
+
+/// The colliders whose `CollisionLayers` changed since their creation.
+///
+/// The groups of a Rapier collider cannot be updated, so the layers of these colliders are
+/// applied by physics hooks during the steps, which are skipped while the set is empty. It
+/// is updated by `apply_physics_changes_system` from the layers added or modified since its
+/// last run.
+#[derive(Default)]
+pub struct ChangedCollisionLayers(pub(crate) HashSet<ColliderHandle>);
+
+impl ChangedCollisionLayers {
+    /// Whether the layers of the given collider changed since its creation.
+    pub fn contains(&self, handle: ColliderHandle) -> bool {
+        self.0.contains(&handle)
+    }
+
+    /// Whether the layers of every collider are the ones it was created with.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A problem met by the physics systems while processing the shipyard components.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PhysicsDiagnostic {
+    /// The `ColliderBuilder` of this entity could not be attached to a rigid-body and
+    /// was discarded, either because the rigid-body was removed or because the
+    /// `ColliderParent` entity was deleted.
+    UnresolvedColliderBuilder(EntityId),
+    /// The `JointBuilderComponent` of the `joint` entity was discarded because the
+    /// `endpoint` entity it attaches was deleted.
+    JointEndpointDeleted {
+        /// The entity holding the joint builder.
+        joint: EntityId,
+        /// The deleted entity.
+        endpoint: EntityId,
+    },
+    /// The `JointBuilderComponent` of this entity was discarded because the rigid-bodies
+    /// it attaches were not created before its timeout.
+    JointBuilderTimedOut(EntityId),
+    /// `step_world_system` stepped the world without the hooks set by
+    /// `UserPhysicsHooks::entity_hooks`, which are only run by
+    /// `step_world_with_entity_hooks_system`.
+    EntityHooksSkipped,
+}
+
+/// A resource collecting the `PhysicsDiagnostic`s reported by the physics systems.
+///
+/// Diagnostics accumulate until they are drained.
+#[derive(Default)]
+pub struct PhysicsDiagnostics {
+    diagnostics: Vec<PhysicsDiagnostic>,
+}
+
+impl PhysicsDiagnostics {
+    /// Iterates through the diagnostics reported since the last drain.
+    pub fn iter(&self) -> impl Iterator<Item = &PhysicsDiagnostic> {
+        self.diagnostics.iter()
+    }
+
+    /// Removes and returns all the diagnostics reported since the last drain.
+    pub fn drain(&mut self) -> impl Iterator<Item = PhysicsDiagnostic> + '_ {
+        self.diagnostics.drain(..)
+    }
+
+    /// Returns `true` if no diagnostic was reported since the last drain.
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, diagnostic: PhysicsDiagnostic) {
+        self.diagnostics.push(diagnostic);
     }
 }
 
@@ -80,12 +437,26 @@ impl EventHandler for EventQueue {
 pub struct SimulationToRenderTime {
     /// Difference between simulation and rendering time
     pub diff: f32,
+    /// Number of physics steps run during the last frame.
+    pub substeps: usize,
+    /// Simulation time, in seconds, discarded during the last frame.
+    pub dropped_time: f32,
+    /// Whether the last frame needed more steps than `RapierConfiguration::max_substeps`
+    /// or accumulated more time than `RapierConfiguration::max_accumulated_time`.
+    pub overflowed: bool,
 }
 
+/// Time elapsed since the last frame, in seconds, used by the physics workload
+/// to step the world.
+#[derive(Default)]
+pub struct PhysicsDeltaTime(pub f32);
+
 /// Custom filters for intersection and contact pairs.
 pub struct UserPhysicsHooks {
     /// Custom user defined physics.
     pub hooks: Box<dyn PhysicsHooks>,
+    /// Hooks using shipyard components, used instead of `hooks` when set.
+    entity_hooks: Option<Box<dyn BorrowPhysicsHooks>>,
 }
 
 impl UserPhysicsHooks {
@@ -93,11 +464,42 @@ impl UserPhysicsHooks {
     pub fn new() -> Self {
         Self {
             hooks: Box::new(()),
+            entity_hooks: None,
         }
     }
 
     /// Set the user defined physics hooks.
     pub fn hooks(&mut self, hooks: impl PhysicsHooks + 'static) {
         self.hooks = Box::new(hooks) as Box<dyn PhysicsHooks>;
+        self.entity_hooks = None;
+    }
+
+    /// Set the user defined physics hooks working with shipyard entities and components.
+    pub fn entity_hooks(&mut self, hooks: impl EntityPhysicsHooks) {
+        self.entity_hooks = Some(Box::new(hooks));
+    }
+
+    /// Whether hooks working with shipyard entities and components are set.
+    pub fn has_entity_hooks(&self) -> bool {
+        self.entity_hooks.is_some()
+    }
+
+    /// Calls `f` with the hooks to use for a physics step.
+    pub(crate) fn with_hooks(
+        &self,
+        all_storages: &AllStorages,
+        entity_maps: &EntityMaps,
+        f: &mut dyn FnMut(&dyn PhysicsHooks) -> Result<(), error::Run>,
+    ) -> Result<(), error::Run> {
+        match &self.entity_hooks {
+            Some(entity_hooks) => entity_hooks.with_hooks(all_storages, entity_maps, f),
+            None => f(&*self.hooks),
+        }
+    }
+}
+
+impl Default for UserPhysicsHooks {
+    fn default() -> Self {
+        Self::new()
     }
 }
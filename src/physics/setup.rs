@@ -0,0 +1,339 @@
+use crate::physics::{
+    ChangedCollisionLayers, CharacterControllerOutput, ColliderHandleComponent, CollisionLayers,
+    EntityMaps, EntityPhysicsHooks, EventQueue, ExternalForce, ExternalImpulse,
+    InterpolatedTransform, JointHandleComponent, PhysicsDeltaTime, PhysicsDiagnostics,
+    PhysicsInterpolationComponent, PhysicsMetrics, PhysicsTransform, RapierConfiguration, RayHits,
+    RigidBodyHandleComponent, ShapeHits, SimulationToRenderTime, StaticGroundBody,
+    UserPhysicsHooks, Velocity,
+};
+
+use crate::rapier::pipeline::{PhysicsHooks, QueryPipeline};
+use rapier::dynamics::{IntegrationParameters, JointSet, RigidBodySet};
+use rapier::geometry::{BroadPhase, ColliderSet, NarrowPhase};
+use rapier::math::Vector;
+use rapier::pipeline::PhysicsPipeline;
+
+use shipyard::{AllStorages, AllStoragesViewMut, IntoIter, ViewMut, World};
+
+/// Builder of the physics resources added to the shipyard World.
+///
+/// ```ignore
+/// PhysicsSetup::new()
+///     .gravity(Vector2::zeros())
+///     .scale(20.0)
+///     .auto_clear_events(false)
+///     .install(&world);
+/// ```
+pub struct PhysicsSetup {
+    configuration: RapierConfiguration,
+    integration_parameters: IntegrationParameters,
+    hooks: UserPhysicsHooks,
+    auto_clear_events: bool,
+    metrics: bool,
+}
+
+impl PhysicsSetup {
+    /// A setup with the default configuration, integration parameters and no hooks,
+    /// as installed by `setup_physics`.
+    pub fn new() -> Self {
+        Self {
+            configuration: RapierConfiguration::default(),
+            integration_parameters: IntegrationParameters::default(),
+            hooks: UserPhysicsHooks::new(),
+            auto_clear_events: true,
+            metrics: false,
+        }
+    }
+
+    /// Sets the whole configuration of the physics simulation.
+    pub fn configuration(mut self, configuration: RapierConfiguration) -> Self {
+        self.configuration = configuration;
+        self
+    }
+
+    /// Sets the whole integration parameters of the physics simulation.
+    pub fn integration_parameters(mut self, integration_parameters: IntegrationParameters) -> Self {
+        self.integration_parameters = integration_parameters;
+        self
+    }
+
+    /// Sets the gravity of the physics simulation.
+    pub fn gravity(mut self, gravity: Vector<f32>) -> Self {
+        self.configuration.gravity = gravity;
+        self
+    }
+
+    /// Sets the scale ratio between the physics world and the `PhysicsTransform`s.
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.configuration.scale = scale;
+        self
+    }
+
+    /// Sets the timestep length of the physics simulation, in seconds.
+    pub fn dt(mut self, dt: f32) -> Self {
+        self.integration_parameters.dt = dt;
+        self
+    }
+
+    /// Sets the user defined physics hooks.
+    pub fn hooks(mut self, hooks: impl PhysicsHooks + 'static) -> Self {
+        self.hooks.hooks(hooks);
+        self
+    }
+
+    /// Sets the user defined physics hooks working with shipyard entities and components.
+    pub fn entity_hooks(mut self, hooks: impl EntityPhysicsHooks) -> Self {
+        self.hooks.entity_hooks(hooks);
+        self
+    }
+
+    /// Sets whether the `EventQueue` is cleared at the start of each step.
+    pub fn auto_clear_events(mut self, auto_clear: bool) -> Self {
+        self.auto_clear_events = auto_clear;
+        self
+    }
+
+    /// Sets whether the performance counters of the `PhysicsPipeline` are enabled and
+    /// collected in a `PhysicsMetrics` resource, disabled by default.
+    pub fn metrics(mut self, enabled: bool) -> Self {
+        self.metrics = enabled;
+        self
+    }
+
+    /// Adds the physics resources to the shipyard World.
+    pub fn install(self, world: &World) {
+        world.run_with_data(install_physics, self).unwrap();
+    }
+
+    /// Adds the physics resources and starts tracking the handle components and the
+    /// components pushed to Rapier.
+    pub(crate) fn add_to(self, all_storages: &AllStorages) {
+        let mut pipeline = PhysicsPipeline::new();
+        if self.metrics {
+            // The performance counters feed the `PhysicsMetrics`.
+            pipeline.counters.enable();
+            all_storages.add_unique(PhysicsMetrics::default());
+        }
+        all_storages.add_unique(pipeline);
+        all_storages.add_unique(QueryPipeline::new());
+        all_storages.add_unique(self.configuration);
+        all_storages.add_unique(self.integration_parameters);
+        all_storages.add_unique(BroadPhase::new());
+        all_storages.add_unique(NarrowPhase::new());
+        all_storages.add_unique(RigidBodySet::new());
+        all_storages.add_unique(ColliderSet::new());
+        all_storages.add_unique(JointSet::new());
+        all_storages.add_unique(self.hooks);
+        all_storages.add_unique(EventQueue::new(self.auto_clear_events));
+        all_storages.add_unique(SimulationToRenderTime::default());
+        all_storages.add_unique(PhysicsDeltaTime::default());
+        all_storages.add_unique(EntityMaps::default());
+        all_storages.add_unique(StaticGroundBody::default());
+        all_storages.add_unique(ChangedCollisionLayers::default());
+        all_storages.add_unique(PhysicsDiagnostics::default());
+
+        all_storages
+            .borrow::<ViewMut<RigidBodyHandleComponent>>()
+            .unwrap()
+            .track_deletion()
+            .track_removal();
+        all_storages
+            .borrow::<ViewMut<ColliderHandleComponent>>()
+            .unwrap()
+            .track_deletion()
+            .track_removal();
+        all_storages
+            .borrow::<ViewMut<JointHandleComponent>>()
+            .unwrap()
+            .track_deletion()
+            .track_removal();
+        all_storages
+            .borrow::<ViewMut<PhysicsTransform>>()
+            .unwrap()
+            .track_modification();
+        all_storages
+            .borrow::<ViewMut<Velocity>>()
+            .unwrap()
+            .track_modification();
+        all_storages
+            .borrow::<ViewMut<CollisionLayers>>()
+            .unwrap()
+            .track_insertion()
+            .track_modification();
+    }
+}
+
+impl Default for PhysicsSetup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn install_physics(setup: PhysicsSetup, all_storages: AllStoragesViewMut) {
+    setup.add_to(&all_storages);
+}
+
+/// Removes the physics resources from the shipyard World, along with the components that
+/// would otherwise refer to the removed sets or carry over to a later setup: the rigid-body,
+/// collider and joint handles, `PhysicsTransform`, `InterpolatedTransform`, `Velocity`,
+/// `ExternalForce`, `ExternalImpulse`, `RayHits`, `ShapeHits` and
+/// `CharacterControllerOutput`. The previous positions of the
+/// `PhysicsInterpolationComponent`s are reset.
+///
+/// The components describing how entities take part in the physics survive the teardown:
+/// the pending `RigidBodyBuilder`, `ColliderBuilder` and `JointBuilderComponent`,
+/// `ColliderParent`, `CollisionLayers`, `PhysicsInterpolationComponent`, `RayCaster`,
+/// `ShapeCaster` and `CharacterController`. The physics can be setup again afterwards, the
+/// pending builders being created by its systems.
+pub fn teardown_physics(all_storages: AllStoragesViewMut) {
+    let _ = all_storages.remove_unique::<PhysicsPipeline>();
+    let _ = all_storages.remove_unique::<QueryPipeline>();
+    let _ = all_storages.remove_unique::<RapierConfiguration>();
+    let _ = all_storages.remove_unique::<IntegrationParameters>();
+    let _ = all_storages.remove_unique::<BroadPhase>();
+    let _ = all_storages.remove_unique::<NarrowPhase>();
+    let _ = all_storages.remove_unique::<RigidBodySet>();
+    let _ = all_storages.remove_unique::<ColliderSet>();
+    let _ = all_storages.remove_unique::<JointSet>();
+    let _ = all_storages.remove_unique::<UserPhysicsHooks>();
+    let _ = all_storages.remove_unique::<EventQueue>();
+    let _ = all_storages.remove_unique::<SimulationToRenderTime>();
+    let _ = all_storages.remove_unique::<PhysicsDeltaTime>();
+    let _ = all_storages.remove_unique::<EntityMaps>();
+    let _ = all_storages.remove_unique::<StaticGroundBody>();
+    let _ = all_storages.remove_unique::<ChangedCollisionLayers>();
+    let _ = all_storages.remove_unique::<PhysicsDiagnostics>();
+    let _ = all_storages.remove_unique::<PhysicsMetrics>();
+
+    clear_components::<RigidBodyHandleComponent>(&all_storages);
+    clear_components::<ColliderHandleComponent>(&all_storages);
+    clear_components::<JointHandleComponent>(&all_storages);
+    clear_components::<PhysicsTransform>(&all_storages);
+    clear_components::<InterpolatedTransform>(&all_storages);
+    clear_components::<Velocity>(&all_storages);
+    clear_components::<ExternalForce>(&all_storages);
+    clear_components::<ExternalImpulse>(&all_storages);
+    clear_components::<RayHits>(&all_storages);
+    clear_components::<ShapeHits>(&all_storages);
+    clear_components::<CharacterControllerOutput>(&all_storages);
+
+    let mut interpolations = all_storages
+        .borrow::<ViewMut<PhysicsInterpolationComponent>>()
+        .unwrap();
+    for mut interpolation in (&mut interpolations).iter() {
+        interpolation.0 = None;
+    }
+}
+
+/// Deletes every component of a storage.
+///
+/// The deleted components are drained from the tracking, a later setup must not try to
+/// remove them from its new sets.
+fn clear_components<T: 'static + Send + Sync>(all_storages: &AllStorages) {
+    let mut storage = all_storages.borrow::<ViewMut<T>>().unwrap();
+    storage.clear();
+    if storage.is_tracking_removal() || storage.is_tracking_deletion() {
+        storage.take_removed_and_deleted();
+    }
+}
+
+#[test]
+fn test_physics_setup_and_teardown() {
+    use rapier::dynamics::RigidBodyBuilder;
+    use rapier::geometry::ColliderBuilder;
+    use rapier::math::Isometry;
+    use shipyard::{Get, UniqueView, View};
+
+    let mut world = World::new();
+    PhysicsSetup::new()
+        .gravity(Vector::zeros())
+        .scale(20.0)
+        .dt(1.0 / 30.0)
+        .auto_clear_events(false)
+        .install(&world);
+    {
+        let (configuration, integration_parameters, events) = world
+            .borrow::<(
+                UniqueView<RapierConfiguration>,
+                UniqueView<IntegrationParameters>,
+                UniqueView<EventQueue>,
+            )>()
+            .unwrap();
+        assert_eq!(configuration.gravity, Vector::zeros());
+        assert_eq!(configuration.scale, 20.0);
+        assert_eq!(integration_parameters.dt, 1.0 / 30.0);
+        assert!(!events.auto_clear);
+    }
+    assert!(world.borrow::<UniqueView<PhysicsMetrics>>().is_err());
+
+    let entity = world.add_entity((
+        RigidBodyBuilder::new_dynamic(),
+        Velocity::default(),
+        PhysicsInterpolationComponent(Some(Isometry::identity())),
+    ));
+    world
+        .run(crate::physics::create_body_and_collider_system)
+        .unwrap();
+    world.add_component(
+        entity,
+        (
+            ExternalImpulse::default(),
+            RayHits::default(),
+            ShapeHits::default(),
+        ),
+    );
+    let pending = world.add_entity((RigidBodyBuilder::new_dynamic(), ColliderBuilder::ball(0.5)));
+
+    world.run(teardown_physics).unwrap();
+    assert!(world.borrow::<UniqueView<RigidBodySet>>().is_err());
+    assert!(world.borrow::<UniqueView<EntityMaps>>().is_err());
+    {
+        let (body_handles, transforms, velocities, impulses, ray_hits, shape_hits) = world
+            .borrow::<(
+                View<RigidBodyHandleComponent>,
+                View<PhysicsTransform>,
+                View<Velocity>,
+                View<ExternalImpulse>,
+                View<RayHits>,
+                View<ShapeHits>,
+            )>()
+            .unwrap();
+        let (body_builders, collider_builders) = world
+            .borrow::<(View<RigidBodyBuilder>, View<ColliderBuilder>)>()
+            .unwrap();
+        assert!(body_handles.is_empty());
+        assert!(transforms.is_empty());
+        assert!(velocities.is_empty());
+        assert!(impulses.is_empty());
+        assert!(ray_hits.is_empty());
+        assert!(shape_hits.is_empty());
+        assert!(body_builders.contains(pending));
+        assert!(collider_builders.contains(pending));
+        let interpolations = world
+            .borrow::<View<PhysicsInterpolationComponent>>()
+            .unwrap();
+        assert_eq!(interpolations.get(entity).unwrap().0, None);
+    }
+
+    // the physics can be setup again, with the pending builders
+    world.run(crate::physics::setup_physics).unwrap();
+    world
+        .run(crate::physics::destroy_body_and_collider_system)
+        .unwrap();
+    world
+        .run(crate::physics::create_body_and_collider_system)
+        .unwrap();
+    let (bodies, colliders) = world
+        .borrow::<(UniqueView<RigidBodySet>, UniqueView<ColliderSet>)>()
+        .unwrap();
+    assert_eq!(bodies.len(), 1);
+    assert_eq!(colliders.len(), 1);
+    let (body_handles, collider_handles) = world
+        .borrow::<(
+            View<RigidBodyHandleComponent>,
+            View<ColliderHandleComponent>,
+        )>()
+        .unwrap();
+    assert!(body_handles.contains(pending));
+    assert!(collider_handles.contains(pending));
+}
@@ -1,72 +1,122 @@
 use crate::physics::{
-    ColliderHandleComponent, EventQueue, UserPhysicsHooks, JointBuilderComponent,
-    JointHandleComponent, PhysicsInterpolationComponent, RapierConfiguration,
-    RigidBodyHandleComponent, SimulationToRenderTime,
+    ChangedCollisionLayers, ColliderHandleComponent, ColliderParent, CollisionLayers, EntityMaps,
+    EventQueue, ExternalForce, ExternalImpulse, InterpolatedTransform, JointBrokenEvent,
+    JointBuilderComponent, JointHandleComponent, PhysicsDiagnostic, PhysicsDiagnostics,
+    PhysicsInterpolationComponent, PhysicsMetrics, PhysicsSetup, PhysicsTransform,
+    RapierConfiguration, RigidBodyHandleComponent, SimulationToRenderTime, StaticGroundBody,
+    SubstepOverflowPolicy, UserPhysicsHooks, Velocity,
 };
 
+use crate::physics::hooks::CollisionLayersHooks;
 use crate::rapier::pipeline::QueryPipeline;
 use rapier::dynamics::{IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodySet};
 use rapier::geometry::{BroadPhase, ColliderBuilder, ColliderSet, NarrowPhase};
-use rapier::pipeline::PhysicsPipeline;
+use rapier::pipeline::{PhysicsHooks, PhysicsPipeline};
 
 use shipyard::{
-    AllStoragesViewMut, EntitiesView, Get, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View,
-    ViewMut,
+    error, AddComponent, AllStorages, AllStoragesViewMut, EntitiesView, EntitiesViewMut, EntityId,
+    Get, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut,
 };
+use std::collections::HashSet;
 
 /// Setup the necessary rapier components to the shipyard World.
+///
+/// Use `PhysicsSetup` to start with a custom configuration.
 pub fn setup_physics(all_storages: AllStoragesViewMut) {
-    all_storages.add_unique(PhysicsPipeline::new());
-    all_storages.add_unique(QueryPipeline::new());
-    all_storages.add_unique(RapierConfiguration::default());
-    all_storages.add_unique(IntegrationParameters::default());
-    all_storages.add_unique(BroadPhase::new());
-    all_storages.add_unique(NarrowPhase::new());
-    all_storages.add_unique(RigidBodySet::new());
-    all_storages.add_unique(ColliderSet::new());
-    all_storages.add_unique(JointSet::new());
-    all_storages.add_unique(UserPhysicsHooks::new());
-    all_storages.add_unique(EventQueue::new(true));
-    all_storages.add_unique(SimulationToRenderTime::default());
-
-    all_storages
-        .borrow::<ViewMut<RigidBodyHandleComponent>>()
-        .unwrap()
-        .track_deletion();
-    all_storages
-        .borrow::<ViewMut<ColliderHandleComponent>>()
-        .unwrap()
-        .track_deletion();
-    all_storages
-        .borrow::<ViewMut<JointHandleComponent>>()
-        .unwrap()
-        .track_deletion();
+    PhysicsSetup::new().add_to(&all_storages);
 }
 
 /// System responsible for creating a Rapier rigid-body and collider from their
 /// builder resources.
+///
+/// A `ColliderBuilder` is attached to the rigid-body of the entity designated by its
+/// `ColliderParent`, if any, or else to the rigid-body of its own entity. Builders whose
+/// parent has no rigid-body yet are kept until it does. Entities with a collider but no
+/// rigid-body nor parent, like static walls or trigger zones, get their collider attached
+/// to the shared `StaticGroundBody`. Builders that cannot be attached are discarded and
+/// reported to `PhysicsDiagnostics`.
+///
+/// The collision and solver groups of the colliders are set from their `CollisionLayers`.
+#[allow(clippy::too_many_arguments)]
 pub fn create_body_and_collider_system(
     entities: EntitiesView,
-    mut bodies: UniqueViewMut<RigidBodySet>,
-    mut colliders: UniqueViewMut<ColliderSet>,
+    configuration: UniqueView<RapierConfiguration>,
+    (mut bodies, mut colliders): (UniqueViewMut<RigidBodySet>, UniqueViewMut<ColliderSet>),
+    (mut entity_maps, mut ground_body, mut diagnostics): (
+        UniqueViewMut<EntityMaps>,
+        UniqueViewMut<StaticGroundBody>,
+        UniqueViewMut<PhysicsDiagnostics>,
+    ),
     mut rigid_body_builders: ViewMut<RigidBodyBuilder>,
     mut rigid_body_handles: ViewMut<RigidBodyHandleComponent>,
-    mut collider_builders: ViewMut<ColliderBuilder>,
+    (mut collider_builders, collider_parents, layers): (
+        ViewMut<ColliderBuilder>,
+        View<ColliderParent>,
+        View<CollisionLayers>,
+    ),
     mut collider_handles: ViewMut<ColliderHandleComponent>,
+    mut transforms: ViewMut<PhysicsTransform>,
 ) {
     for (entity_id, body_builder) in rigid_body_builders.iter().with_id() {
-        let handle = bodies.insert(body_builder.build());
-        entities.add_component(entity_id, &mut rigid_body_handles, handle.into());
-
-        if let Ok(collider_builder) = collider_builders.get(entity_id) {
-            let collider = collider_builder.build();
-            let handle = colliders.insert(collider, handle, &mut bodies);
-            entities.add_component(entity_id, &mut collider_handles, handle.into());
-            collider_builders.delete(entity_id);
-        }
+        let body = body_builder.build();
+        let transform = PhysicsTransform::from_physics(body.position(), configuration.scale);
+        let handle = bodies.insert(body);
+        entities.add_component(
+            entity_id,
+            (&mut rigid_body_handles, &mut transforms),
+            (handle.into(), transform),
+        );
+        entity_maps.insert_body(entity_id, handle);
     }
 
     rigid_body_builders.clear();
+
+    // Builders are drained and the pending ones added back, instead of deleting the processed
+    // ones: shipyard 0.5.0 reads out of bounds when deleting the last component of a storage.
+    let mut pending = Vec::new();
+    for (entity_id, collider_builder) in collider_builders.drain().with_id() {
+        let parent = match collider_parents.get(entity_id) {
+            Ok(&ColliderParent(parent)) => match rigid_body_handles.get(parent) {
+                Ok(body_handle) => Some(body_handle.handle()),
+                Err(_) if entities.is_alive(parent) => {
+                    pending.push((entity_id, collider_builder));
+                    continue;
+                }
+                Err(_) => None,
+            },
+            Err(_) => match rigid_body_handles.get(entity_id) {
+                Ok(body_handle) => Some(body_handle.handle()),
+                Err(_) => Some(ground_body.get_or_insert(&mut bodies)),
+            },
+        };
+
+        let parent = match parent {
+            Some(parent) if bodies.get(parent).is_some() => parent,
+            _ => {
+                diagnostics.push(PhysicsDiagnostic::UnresolvedColliderBuilder(entity_id));
+                continue;
+            }
+        };
+
+        let collider_layers = layers.get(entity_id).ok().or_else(|| {
+            let &ColliderParent(parent) = collider_parents.get(entity_id).ok()?;
+            layers.get(parent).ok()
+        });
+        let collider_builder = match collider_layers {
+            Some(collider_layers) => collider_builder
+                .collision_groups(collider_layers.collision_groups())
+                .solver_groups(collider_layers.solver_groups()),
+            None => collider_builder,
+        };
+
+        let handle = colliders.insert(collider_builder.build(), parent, &mut bodies);
+        entities.add_component(entity_id, &mut collider_handles, handle.into());
+        entity_maps.insert_collider(entity_id, handle);
+    }
+
+    for (entity_id, collider_builder) in pending {
+        entities.add_component(entity_id, &mut collider_builders, collider_builder);
+    }
 }
 
 #[test]
@@ -77,12 +127,18 @@ fn test_create_body_and_collider_system() {
 
     world.add_unique(RigidBodySet::new()).unwrap();
     world.add_unique(ColliderSet::new()).unwrap();
+    world.add_unique(EntityMaps::default()).unwrap();
+    world.add_unique(RapierConfiguration::default()).unwrap();
+    world.add_unique(StaticGroundBody::default()).unwrap();
+    world.add_unique(PhysicsDiagnostics::default()).unwrap();
 
     let body_and_collider_entity =
         world.add_entity((RigidBodyBuilder::new_dynamic(), ColliderBuilder::ball(1.0)));
 
     let body_only_entity = world.add_entity((RigidBodyBuilder::new_static(),));
 
+    let collider_only_entity = world.add_entity((ColliderBuilder::ball(2.0),));
+
     world.run(create_body_and_collider_system).unwrap();
 
     let body_set = world.borrow::<UniqueView<RigidBodySet>>().unwrap();
@@ -110,18 +166,123 @@ fn test_create_body_and_collider_system() {
     // standalone body with no collider, jointed to the attached body
     let standalone_body_handle = rigid_bodies_handles.get(body_only_entity).unwrap().handle();
     assert!(body_set.get(standalone_body_handle).unwrap().is_static());
+
+    // handles can be mapped back to their entities
+    let entity_maps = world.borrow::<UniqueView<EntityMaps>>().unwrap();
+    assert_eq!(
+        entity_maps.body_entity(attached_body_handle),
+        Some(body_and_collider_entity)
+    );
+    assert_eq!(
+        entity_maps.collider_entity(collider_handle),
+        Some(body_and_collider_entity)
+    );
+    assert_eq!(
+        entity_maps.body_handle(body_only_entity),
+        Some(standalone_body_handle)
+    );
+    assert_eq!(entity_maps.collider_handle(body_only_entity), None);
+
+    // standalone collider attached to the static ground body
+    let ground_body_handle = world
+        .borrow::<UniqueView<StaticGroundBody>>()
+        .unwrap()
+        .handle()
+        .unwrap();
+    assert!(body_set.get(ground_body_handle).unwrap().is_static());
+    let standalone_collider_handle = colliders_handles
+        .get(collider_only_entity)
+        .unwrap()
+        .handle();
+    let standalone_collider = collider_set.get(standalone_collider_handle).unwrap();
+    assert_eq!(standalone_collider.parent(), ground_body_handle);
+    assert!(world
+        .borrow::<View<ColliderBuilder>>()
+        .unwrap()
+        .get(collider_only_entity)
+        .is_err());
+    assert!(world
+        .borrow::<UniqueView<PhysicsDiagnostics>>()
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_create_child_colliders() {
+    use shipyard::*;
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+
+    // the child is spawned before its parent has a rigid-body
+    let parent = world.add_entity(());
+    let child = world.add_entity((ColliderBuilder::ball(1.0), ColliderParent(parent)));
+    world.run(create_body_and_collider_system).unwrap();
+    assert!(world
+        .borrow::<View<ColliderBuilder>>()
+        .unwrap()
+        .get(child)
+        .is_ok());
+
+    world.add_component(parent, (RigidBodyBuilder::new_dynamic(),));
+    world.run(create_body_and_collider_system).unwrap();
+    {
+        let (colliders, body_handles, collider_handles) = world
+            .borrow::<(
+                UniqueView<ColliderSet>,
+                View<RigidBodyHandleComponent>,
+                View<ColliderHandleComponent>,
+            )>()
+            .unwrap();
+        let collider = colliders
+            .get(collider_handles.get(child).unwrap().handle())
+            .unwrap();
+        assert_eq!(
+            collider.parent(),
+            body_handles.get(parent).unwrap().handle()
+        );
+    }
+
+    // the builder of a child whose parent was deleted is reported
+    let parent = world.add_entity(());
+    let child = world.add_entity((ColliderBuilder::ball(1.0), ColliderParent(parent)));
+    world.delete_entity(parent);
+    world.run(create_body_and_collider_system).unwrap();
+    assert!(world
+        .borrow::<View<ColliderBuilder>>()
+        .unwrap()
+        .get(child)
+        .is_err());
+    let diagnostics: Vec<_> = world
+        .borrow::<UniqueViewMut<PhysicsDiagnostics>>()
+        .unwrap()
+        .drain()
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![PhysicsDiagnostic::UnresolvedColliderBuilder(child)]
+    );
 }
 
 /// System responsible for creating Rapier joints from their builder resources.
+///
+/// Joint builders stay pending until the rigid-bodies of both their entities exist.
+/// Builders attaching a deleted entity, or exceeding their timeout, are discarded and
+/// reported to `PhysicsDiagnostics`.
+#[allow(clippy::too_many_arguments)]
 pub fn create_joints_system(
     entities: EntitiesView,
     mut bodies: UniqueViewMut<RigidBodySet>,
     mut joints: UniqueViewMut<JointSet>,
+    mut entity_maps: UniqueViewMut<EntityMaps>,
+    mut diagnostics: UniqueViewMut<PhysicsDiagnostics>,
     mut joint_builders: ViewMut<JointBuilderComponent>,
     mut joint_handles: ViewMut<JointHandleComponent>,
     bodies_handles: View<RigidBodyHandleComponent>,
 ) {
-    for (entity_id, joint_builder) in joint_builders.iter().with_id() {
+    // Drained and added back like the collider builders, see `create_body_and_collider_system`.
+    let mut pending = Vec::new();
+    for (entity_id, mut joint_builder) in joint_builders.drain().with_id() {
         let body1 = bodies_handles.get(joint_builder.entity1);
         let body2 = bodies_handles.get(joint_builder.entity2);
         if let (Ok(body1), Ok(body2)) = (body1, body2) {
@@ -136,21 +297,230 @@ pub fn create_joints_system(
                 &mut joint_handles,
                 JointHandleComponent::new(handle, joint_builder.entity1, joint_builder.entity2),
             );
+            entity_maps.insert_joint(entity_id, handle);
+            continue;
+        }
+
+        let deleted_endpoint = [joint_builder.entity1, joint_builder.entity2]
+            .iter()
+            .copied()
+            .find(|endpoint| !entities.is_alive(*endpoint));
+        if let Some(endpoint) = deleted_endpoint {
+            diagnostics.push(PhysicsDiagnostic::JointEndpointDeleted {
+                joint: entity_id,
+                endpoint,
+            });
+            continue;
+        }
+
+        joint_builder.attempts += 1;
+        match joint_builder.timeout {
+            Some(timeout) if joint_builder.attempts > timeout => {
+                diagnostics.push(PhysicsDiagnostic::JointBuilderTimedOut(entity_id));
+            }
+            _ => pending.push((entity_id, joint_builder)),
         }
     }
 
-    joint_builders.clear();
+    for (entity_id, joint_builder) in pending {
+        entities.add_component(entity_id, &mut joint_builders, joint_builder);
+    }
+}
+
+#[test]
+fn test_create_joints_system() {
+    use shipyard::*;
+
+    use rapier::dynamics::BallJoint;
+    use rapier::math::Point;
+
+    let joint = || BallJoint::new(Point::origin(), Point::origin());
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+
+    // the joint is spawned before the bodies are created
+    let entity1 = world.add_entity((RigidBodyBuilder::new_static(),));
+    let entity2 = world.add_entity((RigidBodyBuilder::new_dynamic(),));
+    let joint_entity = world.add_entity((JointBuilderComponent::new(joint(), entity1, entity2),));
+    world.run(create_joints_system).unwrap();
+    assert!(world
+        .borrow::<View<JointBuilderComponent>>()
+        .unwrap()
+        .get(joint_entity)
+        .is_ok());
+
+    world.run(create_body_and_collider_system).unwrap();
+    world.run(create_joints_system).unwrap();
+    {
+        let (joints, joint_handles) = world
+            .borrow::<(UniqueView<JointSet>, View<JointHandleComponent>)>()
+            .unwrap();
+        let joint_handle = joint_handles.get(joint_entity).unwrap();
+        assert!(joints.get(joint_handle.handle()).is_some());
+        assert_eq!(joint_handle.entity1(), entity1);
+        assert_eq!(joint_handle.entity2(), entity2);
+    }
+
+    // an endpoint is deleted before the joint is created
+    let deleted = world.add_entity(());
+    let broken_joint = world.add_entity((JointBuilderComponent::new(joint(), entity1, deleted),));
+    world.delete_entity(deleted);
+
+    // an endpoint never gets a rigid-body
+    let bodyless = world.add_entity(());
+    let timed_out_joint =
+        world.add_entity((JointBuilderComponent::new(joint(), entity1, bodyless).timeout(2),));
+
+    for _ in 0..3 {
+        world.run(create_joints_system).unwrap();
+    }
+
+    assert!(world
+        .borrow::<View<JointBuilderComponent>>()
+        .unwrap()
+        .is_empty());
+    let diagnostics: Vec<_> = world
+        .borrow::<UniqueViewMut<PhysicsDiagnostics>>()
+        .unwrap()
+        .drain()
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            PhysicsDiagnostic::JointEndpointDeleted {
+                joint: broken_joint,
+                endpoint: deleted,
+            },
+            PhysicsDiagnostic::JointBuilderTimedOut(timed_out_joint),
+        ]
+    );
 }
 
 /// System responsible for performing one timestep of the physics world.
+///
+/// The contact and intersection pairs are filtered by the `UserPhysicsHooks::hooks`, and by
+/// the `CollisionLayers` changed since the creation of their colliders, as listed in the
+/// `ChangedCollisionLayers`. Hooks set with `UserPhysicsHooks::entity_hooks` borrow their own
+/// storages, they are only run by `step_world_with_entity_hooks_system` and reported as
+/// skipped to `PhysicsDiagnostics` here.
+///
+/// The timings of the steps and the size of the physics world are recorded in the
+/// `PhysicsMetrics` resource, if there is one.
+#[allow(clippy::too_many_arguments)]
 pub fn step_world_system(
     delta_seconds: f32,
+    (user_hooks, entity_maps, layers, changed_layers): (
+        UniqueView<UserPhysicsHooks>,
+        UniqueView<EntityMaps>,
+        View<CollisionLayers>,
+        UniqueView<ChangedCollisionLayers>,
+    ),
+    (sim_to_render_time, mut diagnostics): (
+        UniqueViewMut<SimulationToRenderTime>,
+        UniqueViewMut<PhysicsDiagnostics>,
+    ),
+    parameters: (
+        UniqueView<RapierConfiguration>,
+        UniqueView<IntegrationParameters>,
+    ),
+    pipelines: (UniqueViewMut<PhysicsPipeline>, UniqueViewMut<QueryPipeline>),
+    phases: (UniqueViewMut<BroadPhase>, UniqueViewMut<NarrowPhase>),
+    sets: (UniqueViewMut<RigidBodySet>, UniqueViewMut<ColliderSet>),
+    joints: UniqueViewMut<JointSet>,
+    events: (
+        UniqueViewMut<EventQueue>,
+        Option<UniqueViewMut<PhysicsMetrics>>,
+    ),
+    components: (
+        View<RigidBodyHandleComponent>,
+        ViewMut<PhysicsInterpolationComponent>,
+        View<ExternalForce>,
+    ),
+) {
+    if user_hooks.has_entity_hooks() {
+        diagnostics.push(PhysicsDiagnostic::EntityHooksSkipped);
+    }
+    with_layers(
+        &*user_hooks.hooks,
+        &layers,
+        &entity_maps,
+        &changed_layers,
+        |hooks| {
+            step_world_with_hooks(
+                (delta_seconds, hooks, &entity_maps),
+                sim_to_render_time,
+                parameters,
+                pipelines,
+                phases,
+                sets,
+                joints,
+                events,
+                components,
+            )
+        },
+    );
+}
+
+/// System performing one timestep of the physics world like `step_world_system`, with the
+/// hooks set by `UserPhysicsHooks::entity_hooks` borrowing their views for the duration
+/// of the step.
+///
+/// It borrows the whole World, so it cannot run in parallel with other systems. The world
+/// is not stepped and an error is returned if the views of the hooks cannot be borrowed,
+/// or if they borrow a storage the step itself borrows mutably.
+pub fn step_world_with_entity_hooks_system(
+    delta_seconds: f32,
+    all_storages: AllStoragesViewMut,
+) -> Result<(), error::Run> {
+    step_world(delta_seconds, &all_storages)
+}
+
+/// Steps the physics world with the `UserPhysicsHooks`, whose views are borrowed
+/// for the duration of the step, and the `CollisionLayers`.
+pub(crate) fn step_world(delta_seconds: f32, all_storages: &AllStorages) -> Result<(), error::Run> {
+    let (user_hooks, entity_maps, layers, changed_layers) = all_storages.borrow::<(
+        UniqueView<UserPhysicsHooks>,
+        UniqueView<EntityMaps>,
+        View<CollisionLayers>,
+        UniqueView<ChangedCollisionLayers>,
+    )>()?;
+    user_hooks.with_hooks(all_storages, &entity_maps, &mut |hooks| {
+        with_layers(hooks, &layers, &entity_maps, &changed_layers, |hooks| {
+            all_storages.run_with_data(step_world_with_hooks, (delta_seconds, hooks, &*entity_maps))
+        })
+    })
+}
+
+/// Calls `f` with the hooks applying the `CollisionLayers` on top of `hooks`, if the layers
+/// of a collider changed since its creation.
+fn with_layers<R>(
+    hooks: &dyn PhysicsHooks,
+    layers: &View<CollisionLayers>,
+    entity_maps: &EntityMaps,
+    changed_layers: &ChangedCollisionLayers,
+    f: impl FnOnce(&dyn PhysicsHooks) -> R,
+) -> R {
+    if changed_layers.is_empty() {
+        f(hooks)
+    } else {
+        f(&CollisionLayersHooks {
+            hooks,
+            layers,
+            entity_maps,
+            changed_layers,
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn step_world_with_hooks(
+    (delta_seconds, hooks, entity_maps): (f32, &dyn PhysicsHooks, &EntityMaps),
     mut sim_to_render_time: UniqueViewMut<SimulationToRenderTime>,
     (configuration, integration_parameters): (
         UniqueView<RapierConfiguration>,
         UniqueView<IntegrationParameters>,
     ),
-    user_hooks: UniqueView<UserPhysicsHooks>,
     (mut pipeline, mut query_pipeline): (
         UniqueViewMut<PhysicsPipeline>,
         UniqueViewMut<QueryPipeline>,
@@ -158,26 +528,63 @@ pub fn step_world_system(
     (mut broad_phase, mut narrow_phase): (UniqueViewMut<BroadPhase>, UniqueViewMut<NarrowPhase>),
     (mut bodies, mut colliders): (UniqueViewMut<RigidBodySet>, UniqueViewMut<ColliderSet>),
     mut joints: UniqueViewMut<JointSet>,
-    events: UniqueViewMut<EventQueue>,
-    (rigid_bodies_handles, mut physics_interpolation): (
+    (events, mut metrics): (
+        UniqueViewMut<EventQueue>,
+        Option<UniqueViewMut<PhysicsMetrics>>,
+    ),
+    (rigid_bodies_handles, mut physics_interpolation, external_forces): (
         View<RigidBodyHandleComponent>,
         ViewMut<PhysicsInterpolationComponent>,
+        View<ExternalForce>,
     ),
 ) {
     if events.auto_clear {
         events.clear();
     }
 
+    sim_to_render_time.substeps = 0;
+    sim_to_render_time.dropped_time = 0.0;
+    sim_to_render_time.overflowed = false;
+    if let Some(metrics) = &mut metrics {
+        metrics.begin_frame();
+    }
+
     if configuration.time_dependent_number_of_timesteps {
         sim_to_render_time.diff += delta_seconds;
 
         let sim_dt = integration_parameters.dt;
+        let policy = configuration.substep_overflow_policy;
+        let max_substeps = match policy {
+            SubstepOverflowPolicy::Report => usize::MAX,
+            _ => configuration.max_substeps,
+        };
+
+        if sim_to_render_time.diff > configuration.max_accumulated_time {
+            sim_to_render_time.overflowed = true;
+            if policy != SubstepOverflowPolicy::Report {
+                sim_to_render_time.dropped_time +=
+                    sim_to_render_time.diff - configuration.max_accumulated_time;
+                sim_to_render_time.diff = configuration.max_accumulated_time;
+            }
+        }
+
+        let mut num_steps = 0;
         while sim_to_render_time.diff >= sim_dt {
+            if num_steps == max_substeps {
+                sim_to_render_time.overflowed = true;
+                if policy == SubstepOverflowPolicy::DropExcessTime {
+                    let excess = sim_to_render_time.diff - sim_to_render_time.diff % sim_dt;
+                    sim_to_render_time.dropped_time += excess;
+                    sim_to_render_time.diff -= excess;
+                }
+                break;
+            }
+
             if configuration.physics_pipeline_active {
                 // NOTE: in this comparison we do the same computations we
                 // will do for the next `while` iteration test, to make sure we
                 // don't get bit by potential float inaccuracy.
-                if sim_to_render_time.diff - sim_dt < sim_dt {
+                if sim_to_render_time.diff - sim_dt < sim_dt || num_steps + 1 == max_substeps {
                     // This is the last simulation step to be executed in the loop
                     // Update the previous state transforms
                     for (body_handle, mut previous_state) in
@@ -188,6 +595,12 @@ pub fn step_world_system(
                         }
                     }
                 }
+                apply_external_forces(
+                    &mut bodies,
+                    configuration.scale,
+                    &rigid_bodies_handles,
+                    &external_forces,
+                );
                 pipeline.step(
                     &configuration.gravity,
                     &integration_parameters,
@@ -196,13 +609,24 @@ pub fn step_world_system(
                     &mut bodies,
                     &mut colliders,
                     &mut joints,
-                    &*user_hooks.hooks,
+                    hooks,
                     &*events,
                 );
+                if let Some(metrics) = &mut metrics {
+                    metrics.record_step(&pipeline.counters);
+                }
+                sim_to_render_time.substeps += 1;
             }
             sim_to_render_time.diff -= sim_dt;
+            num_steps += 1;
         }
     } else if configuration.physics_pipeline_active {
+        apply_external_forces(
+            &mut bodies,
+            configuration.scale,
+            &rigid_bodies_handles,
+            &external_forces,
+        );
         pipeline.step(
             &configuration.gravity,
             &integration_parameters,
@@ -211,38 +635,760 @@ pub fn step_world_system(
             &mut bodies,
             &mut colliders,
             &mut joints,
-            &*user_hooks.hooks,
+            hooks,
             &*events,
         );
+        if let Some(metrics) = &mut metrics {
+            metrics.record_step(&pipeline.counters);
+        }
+        sim_to_render_time.substeps = 1;
+    }
+
+    if let Some(metrics) = &mut metrics {
+        metrics.end_frame(delta_seconds, &narrow_phase, &bodies, &colliders, &joints);
     }
 
+    events.resolve_entities(entity_maps);
+
     if configuration.query_pipeline_active {
-        query_pipeline.update(&mut bodies, &colliders);
+        query_pipeline.update(&bodies, &colliders);
     }
 }
 
+#[test]
+fn test_step_world_system_max_substeps() {
+    use shipyard::*;
+
+    let world = World::new();
+    world.run(setup_physics).unwrap();
+    world
+        .borrow::<UniqueViewMut<RapierConfiguration>>()
+        .unwrap()
+        .time_dependent_number_of_timesteps = true;
+    let dt = world
+        .borrow::<UniqueView<IntegrationParameters>>()
+        .unwrap()
+        .dt;
+
+    // a one second hitch only runs `max_substeps` steps and drops the rest
+    world.run_with_data(step_world_system, 1.0).unwrap();
+    {
+        let sim_to_render_time = world
+            .borrow::<UniqueView<SimulationToRenderTime>>()
+            .unwrap();
+        assert_eq!(sim_to_render_time.substeps, 8);
+        assert!(sim_to_render_time.overflowed);
+        assert!(sim_to_render_time.diff < dt);
+        assert!(sim_to_render_time.dropped_time > 0.8);
+    }
+
+    // in slow-motion, the excess is kept for the next frames
+    world
+        .borrow::<UniqueViewMut<RapierConfiguration>>()
+        .unwrap()
+        .substep_overflow_policy = SubstepOverflowPolicy::SlowMotion;
+    world.run_with_data(step_world_system, 1.0).unwrap();
+    {
+        let sim_to_render_time = world
+            .borrow::<UniqueView<SimulationToRenderTime>>()
+            .unwrap();
+        assert_eq!(sim_to_render_time.substeps, 8);
+        assert!((sim_to_render_time.diff - (0.25 - 8.0 * dt)).abs() < 1.0e-5);
+    }
+
+    // when only reporting, every step is run
+    world
+        .borrow::<UniqueViewMut<RapierConfiguration>>()
+        .unwrap()
+        .substep_overflow_policy = SubstepOverflowPolicy::Report;
+    world.run_with_data(step_world_system, 0.5).unwrap();
+    let sim_to_render_time = world
+        .borrow::<UniqueView<SimulationToRenderTime>>()
+        .unwrap();
+    assert!(sim_to_render_time.substeps > 8);
+    assert!(sim_to_render_time.overflowed);
+    assert_eq!(sim_to_render_time.dropped_time, 0.0);
+    assert!(sim_to_render_time.diff < dt);
+}
+
+/// Rapier resets the forces of every rigid-body after each timestep, so they
+/// must be applied again before each one of them.
+fn apply_external_forces(
+    bodies: &mut RigidBodySet,
+    scale: f32,
+    body_handles: &View<RigidBodyHandleComponent>,
+    external_forces: &View<ExternalForce>,
+) {
+    for (body_handle, external_force) in (body_handles, external_forces).iter() {
+        if let Some(body) = bodies.get_mut(body_handle.handle()) {
+            body.apply_force(external_force.force / scale, true);
+            body.apply_torque(external_force.torque, true);
+        }
+    }
+}
+
+#[test]
+fn test_step_world_system_collision_events() {
+    use crate::physics::CollisionEvent;
+    use rapier::math::{Translation, Vector};
+    use shipyard::*;
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+
+    let ground = world.add_entity((RigidBodyBuilder::new_static(), ColliderBuilder::ball(1.0)));
+    let ball = world.add_entity((
+        RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::y() * 2.0).into()),
+        ColliderBuilder::ball(0.5),
+    ));
+
+    world.run(create_body_and_collider_system).unwrap();
+
+    let mut started = None;
+    for _ in 0..60 {
+        world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+        let events = world.borrow::<UniqueView<EventQueue>>().unwrap();
+        if let Ok(event) = events.collision_events.pop() {
+            started = Some(event);
+            break;
+        }
+    }
+
+    match started {
+        Some(CollisionEvent::CollisionStarted(pair)) => {
+            let mut entities = [pair.entity1, pair.entity2];
+            entities.sort_by_key(|entity| entity.index());
+            assert_eq!(entities, [ground, ball]);
+        }
+        event => panic!("expected a CollisionStarted event, got {:?}", event),
+    }
+}
+
+/// System responsible for pushing the physics components modified by the user
+/// to their Rapier rigid-bodies.
+///
+/// `PhysicsTransform` and `Velocity` components modified since the last run of this
+/// system are written to their rigid-body, and non-zero `ExternalImpulse`s are applied
+/// then reset. The colliders of the `CollisionLayers` added or modified since the last run
+/// are added to the `ChangedCollisionLayers` if their groups differ from the layers. This
+/// should run before `step_world_system`.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_physics_changes_system(
+    configuration: UniqueView<RapierConfiguration>,
+    mut bodies: UniqueViewMut<RigidBodySet>,
+    body_handles: View<RigidBodyHandleComponent>,
+    mut transforms: ViewMut<PhysicsTransform>,
+    mut velocities: ViewMut<Velocity>,
+    mut impulses: ViewMut<ExternalImpulse>,
+    (colliders, collider_handles, entity_maps): (
+        UniqueView<ColliderSet>,
+        View<ColliderHandleComponent>,
+        UniqueView<EntityMaps>,
+    ),
+    (mut layers, mut changed_layers): (
+        ViewMut<CollisionLayers>,
+        UniqueViewMut<ChangedCollisionLayers>,
+    ),
+) {
+    for (body_handle, transform) in (&body_handles, transforms.modified()).iter() {
+        if let Some(body) = bodies.get_mut(body_handle.handle()) {
+            let position = transform.to_physics(configuration.scale);
+            if body.is_kinematic() {
+                body.set_next_kinematic_position(position);
+            } else {
+                body.set_position(position, true);
+            }
+        }
+    }
+    transforms.clear_all_modified();
+
+    for (body_handle, velocity) in (&body_handles, velocities.modified()).iter() {
+        if let Some(body) = bodies.get_mut(body_handle.handle()) {
+            body.set_linvel(velocity.linvel / configuration.scale, true);
+            body.set_angvel(velocity.angvel, true);
+        }
+    }
+    velocities.clear_all_modified();
+
+    for (body_handle, mut impulse) in (&body_handles, &mut impulses).iter() {
+        if *impulse == ExternalImpulse::default() {
+            continue;
+        }
+        if let Some(body) = bodies.get_mut(body_handle.handle()) {
+            body.apply_impulse(impulse.impulse / configuration.scale, true);
+            body.apply_torque_impulse(impulse.torque_impulse, true);
+        }
+        *impulse = ExternalImpulse::default();
+    }
+
+    // The layers of an entity apply to its own collider and to the colliders of its body.
+    for (entity, _) in layers.inserted_or_modified().iter().with_id() {
+        if let Ok(collider_handle) = collider_handles.get(entity) {
+            changed_layers.0.insert(collider_handle.handle());
+        }
+        if let Ok(body_handle) = body_handles.get(entity) {
+            if let Some(body) = bodies.get(body_handle.handle()) {
+                changed_layers.0.extend(body.colliders().iter().copied());
+            }
+        }
+    }
+    layers.clear_all_inserted_and_modified();
+    changed_layers
+        .0
+        .retain(|&handle| match colliders.get(handle) {
+            Some(collider) => {
+                CollisionLayersHooks::layers_changed(&layers, &entity_maps, handle, collider)
+            }
+            None => false,
+        });
+}
+
+/// System responsible for writing the position and velocity of each Rapier rigid-body
+/// to its entity's `PhysicsTransform` and `Velocity`.
+///
+/// This should run after `step_world_system`. Components modified by the user since the
+/// last `apply_physics_changes_system` are left untouched: on conflict, the change made
+/// on the shipyard side wins and is pushed to Rapier before the next step.
+pub fn sync_transforms_system(
+    configuration: UniqueView<RapierConfiguration>,
+    bodies: UniqueView<RigidBodySet>,
+    body_handles: View<RigidBodyHandleComponent>,
+    mut transforms: ViewMut<PhysicsTransform>,
+    mut velocities: ViewMut<Velocity>,
+) {
+    for (entity, body_handle) in body_handles.iter().with_id() {
+        let body = match bodies.get(body_handle.handle()) {
+            Some(body) => body,
+            None => continue,
+        };
+
+        if !transforms.is_modified(entity) {
+            if let Ok(mut transform) = (&mut transforms).get(entity) {
+                *transform = PhysicsTransform::from_physics(body.position(), configuration.scale);
+            }
+            transforms.clear_modified(entity);
+        }
+
+        if !velocities.is_modified(entity) {
+            if let Ok(mut velocity) = (&mut velocities).get(entity) {
+                velocity.linvel = body.linvel() * configuration.scale;
+                #[cfg(feature = "dim2")]
+                {
+                    velocity.angvel = body.angvel();
+                }
+                #[cfg(feature = "dim3")]
+                {
+                    velocity.angvel = *body.angvel();
+                }
+            }
+            velocities.clear_modified(entity);
+        }
+    }
+}
+
+#[test]
+fn test_sync_transforms_system() {
+    use rapier::math::{Translation, Vector};
+    use shipyard::*;
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+    world
+        .borrow::<UniqueViewMut<RapierConfiguration>>()
+        .unwrap()
+        .scale = 10.0;
+
+    let entity = world.add_entity((
+        RigidBodyBuilder::new_dynamic().position(Translation::from(Vector::y() * 2.0).into()),
+        ColliderBuilder::ball(0.5),
+    ));
+
+    world.run(create_body_and_collider_system).unwrap();
+    {
+        let transforms = world.borrow::<View<PhysicsTransform>>().unwrap();
+        assert_eq!(
+            transforms.get(entity).unwrap().translation(),
+            Vector::y() * 20.0
+        );
+    }
+
+    world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+    world.run(sync_transforms_system).unwrap();
+
+    let (bodies, body_handles, transforms) = world
+        .borrow::<(
+            UniqueView<RigidBodySet>,
+            View<RigidBodyHandleComponent>,
+            View<PhysicsTransform>,
+        )>()
+        .unwrap();
+    let body = bodies
+        .get(body_handles.get(entity).unwrap().handle())
+        .unwrap();
+    let transform = transforms.get(entity).unwrap();
+    assert!(transform.translation().y < 20.0);
+    assert_eq!(
+        transform.translation(),
+        body.position().translation.vector * 10.0
+    );
+    assert_eq!(
+        transform.to_physics(10.0).translation,
+        body.position().translation
+    );
+}
+
+#[test]
+fn test_apply_physics_changes_system() {
+    use rapier::math::{Translation, Vector};
+    use shipyard::*;
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+
+    let entity = world.add_entity((RigidBodyBuilder::new_dynamic(), ColliderBuilder::ball(0.5)));
+    world.run(create_body_and_collider_system).unwrap();
+    world.add_component(entity, (Velocity::default(),));
+
+    // teleport the body from the shipyard side
+    {
+        let mut transforms = world.borrow::<ViewMut<PhysicsTransform>>().unwrap();
+        (&mut transforms).get(entity).unwrap().0 = Translation::from(Vector::y() * 5.0).into();
+    }
+    world.run(apply_physics_changes_system).unwrap();
+    {
+        let (bodies, body_handles) = world
+            .borrow::<(UniqueView<RigidBodySet>, View<RigidBodyHandleComponent>)>()
+            .unwrap();
+        let body = bodies
+            .get(body_handles.get(entity).unwrap().handle())
+            .unwrap();
+        assert_eq!(body.position().translation.vector, Vector::y() * 5.0);
+    }
+
+    // a velocity edited after the last apply is not overwritten by the sync
+    {
+        let mut velocities = world.borrow::<ViewMut<Velocity>>().unwrap();
+        (&mut velocities).get(entity).unwrap().linvel = Vector::x() * 3.0;
+    }
+    world.run_with_data(step_world_system, 1.0 / 60.0).unwrap();
+    world.run(sync_transforms_system).unwrap();
+    {
+        let (transforms, velocities) = world
+            .borrow::<(View<PhysicsTransform>, View<Velocity>)>()
+            .unwrap();
+        assert!(transforms.get(entity).unwrap().translation().y < 5.0);
+        assert_eq!(velocities.get(entity).unwrap().linvel, Vector::x() * 3.0);
+    }
+
+    world.run(apply_physics_changes_system).unwrap();
+    let (bodies, body_handles) = world
+        .borrow::<(UniqueView<RigidBodySet>, View<RigidBodyHandleComponent>)>()
+        .unwrap();
+    let body = bodies
+        .get(body_handles.get(entity).unwrap().handle())
+        .unwrap();
+    assert_eq!(*body.linvel(), Vector::x() * 3.0);
+}
+
+/// System responsible for blending the previous and current position of each
+/// rigid-body with a `PhysicsInterpolationComponent` into its `InterpolatedTransform`.
+///
+/// The blend factor is the simulation time left over by `step_world_system`,
+/// `SimulationToRenderTime::diff`, divided by `IntegrationParameters::dt`: the translation
+/// is linearly interpolated and the rotation spherically interpolated. Without a previous
+/// position, which is only recorded when `time_dependent_number_of_timesteps` is enabled,
+/// the current position is used as is.
+///
+/// This should run after `step_world_system`.
+pub fn interpolate_transforms_system(
+    entities: EntitiesView,
+    (configuration, integration_parameters, sim_to_render_time): (
+        UniqueView<RapierConfiguration>,
+        UniqueView<IntegrationParameters>,
+        UniqueView<SimulationToRenderTime>,
+    ),
+    bodies: UniqueView<RigidBodySet>,
+    body_handles: View<RigidBodyHandleComponent>,
+    physics_interpolation: View<PhysicsInterpolationComponent>,
+    mut interpolated_transforms: ViewMut<InterpolatedTransform>,
+) {
+    let alpha = (sim_to_render_time.diff / integration_parameters.dt).clamp(0.0, 1.0);
+
+    for (entity, (body_handle, previous_state)) in
+        (&body_handles, &physics_interpolation).iter().with_id()
+    {
+        if let Some(body) = bodies.get(body_handle.handle()) {
+            let mut position = match previous_state.0 {
+                Some(previous) => previous.lerp_slerp(body.position(), alpha),
+                None => *body.position(),
+            };
+            position.translation.vector *= configuration.scale;
+            entities.add_component(
+                entity,
+                &mut interpolated_transforms,
+                InterpolatedTransform(position),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_interpolate_transforms_system() {
+    use rapier::math::Vector;
+    use shipyard::*;
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+    {
+        let mut configuration = world
+            .borrow::<UniqueViewMut<RapierConfiguration>>()
+            .unwrap();
+        configuration.time_dependent_number_of_timesteps = true;
+        configuration.gravity = Vector::zeros();
+    }
+
+    let entity = world.add_entity((
+        RigidBodyBuilder::new_dynamic(),
+        PhysicsInterpolationComponent::default(),
+    ));
+    world.run(create_body_and_collider_system).unwrap();
+    world
+        .run(
+            |mut bodies: UniqueViewMut<RigidBodySet>,
+             body_handles: View<RigidBodyHandleComponent>| {
+                let body = bodies
+                    .get_mut(body_handles.get(entity).unwrap().handle())
+                    .unwrap();
+                body.set_linvel(Vector::x() * 6.0, true);
+            },
+        )
+        .unwrap();
+
+    let dt = world
+        .borrow::<UniqueView<IntegrationParameters>>()
+        .unwrap()
+        .dt;
+    world.run_with_data(step_world_system, dt * 1.5).unwrap();
+    world.run(interpolate_transforms_system).unwrap();
+
+    let (bodies, body_handles, interpolated_transforms) = world
+        .borrow::<(
+            UniqueView<RigidBodySet>,
+            View<RigidBodyHandleComponent>,
+            View<InterpolatedTransform>,
+        )>()
+        .unwrap();
+    let body = bodies
+        .get(body_handles.get(entity).unwrap().handle())
+        .unwrap();
+    let interpolated = interpolated_transforms.get(entity).unwrap().translation();
+    // one step was taken from the origin, and half a step is left over
+    assert!((body.position().translation.vector.x - 6.0 * dt).abs() < 1.0e-5);
+    assert!((interpolated.x - 3.0 * dt).abs() < 1.0e-5);
+}
+
 /// System responsible for removing joints, colliders, and bodies that have
 /// been removed from the shipyard World.
+///
+/// Both deleted entities and handle components removed with `remove` are handled.
+/// Removing a `RigidBodyHandleComponent` also removes the colliders attached to that
+/// body, including those on other entities through a `ColliderParent`.
+///
+/// Joints attaching an entity whose rigid-body was removed are removed by Rapier as well:
+/// their entity loses its `JointHandleComponent`, or is deleted if
+/// `RapierConfiguration::delete_broken_joint_entities` is set, and a `JointBrokenEvent`
+/// is pushed to the `EventQueue`. Other components of a deleted joint entity are left
+/// behind: give it none, or delete it yourself on the `JointBrokenEvent` instead.
+#[allow(clippy::too_many_arguments)]
 pub fn destroy_body_and_collider_system(
-    mut bodies: UniqueViewMut<RigidBodySet>,
-    mut colliders: UniqueViewMut<ColliderSet>,
-    mut joints: UniqueViewMut<JointSet>,
+    mut entities: EntitiesViewMut,
+    configuration: UniqueView<RapierConfiguration>,
+    (mut bodies, mut colliders, mut joints): (
+        UniqueViewMut<RigidBodySet>,
+        UniqueViewMut<ColliderSet>,
+        UniqueViewMut<JointSet>,
+    ),
+    mut entity_maps: UniqueViewMut<EntityMaps>,
+    events: UniqueView<EventQueue>,
     mut collider_handles: ViewMut<ColliderHandleComponent>,
     mut joint_handles: ViewMut<JointHandleComponent>,
     mut body_handles: ViewMut<RigidBodyHandleComponent>,
 ) {
-    for (entity, body_handle) in body_handles.take_deleted().iter() {
-        bodies.remove(body_handle.handle(), &mut colliders, &mut joints);
+    // Removed components are not handed back by shipyard, their handle is found
+    // in the entity maps instead.
+    let (removed, deleted) = body_handles.take_removed_and_deleted();
+    let removed_bodies = removed
+        .into_iter()
+        .filter(|entity| !body_handles.contains(*entity))
+        .filter_map(|entity| Some((entity, entity_maps.body_handle(entity)?)));
+    let deleted_bodies = deleted
+        .into_iter()
+        .map(|(entity, body_handle)| (entity, body_handle.handle()));
+
+    let mut removed_body_entities = HashSet::new();
+    for (entity, body_handle) in removed_bodies.chain(deleted_bodies).collect::<Vec<_>>() {
+        entity_maps.remove_body(body_handle);
+        if let Some(body) = bodies.remove(body_handle, &mut colliders, &mut joints) {
+            // Colliders attached through a `ColliderParent` live on other entities.
+            for collider in body.colliders() {
+                if let Some(collider_entity) = entity_maps.collider_entity(*collider) {
+                    delete_component(&mut collider_handles, collider_entity);
+                }
+                entity_maps.remove_collider(*collider);
+            }
+        }
+        removed_body_entities.insert(entity);
 
         // Removing a body also removes its colliders and joints. If they were
         // not also removed then we must remove them here.
-        joint_handles.delete(*entity);
-        collider_handles.delete(*entity);
+        delete_component(&mut joint_handles, entity);
+        delete_component(&mut collider_handles, entity);
     }
-    for (_, collider_handle) in collider_handles.take_deleted().iter() {
-        colliders.remove(collider_handle.handle(), &mut bodies, true);
+
+    let mut broken_joint_entities = Vec::new();
+    if !removed_body_entities.is_empty() {
+        entity_maps.retain_joints(&joints);
+        for (joint_entity, joint_handle) in joint_handles.iter().with_id() {
+            if removed_body_entities.contains(&joint_handle.entity1())
+                || removed_body_entities.contains(&joint_handle.entity2())
+            {
+                let _ = events.broken_joints.push(JointBrokenEvent {
+                    joint: joint_entity,
+                    entity1: joint_handle.entity1(),
+                    entity2: joint_handle.entity2(),
+                });
+                broken_joint_entities.push(joint_entity);
+            }
+        }
+        for joint_entity in &broken_joint_entities {
+            delete_component(&mut joint_handles, *joint_entity);
+        }
+    }
+
+    let (removed, deleted) = collider_handles.take_removed_and_deleted();
+    let removed_colliders = removed
+        .into_iter()
+        .filter(|entity| !collider_handles.contains(*entity))
+        .filter_map(|entity| entity_maps.collider_handle(entity));
+    let deleted_colliders = deleted
+        .into_iter()
+        .map(|(_, collider_handle)| collider_handle.handle());
+    for collider_handle in removed_colliders
+        .chain(deleted_colliders)
+        .collect::<Vec<_>>()
+    {
+        entity_maps.remove_collider(collider_handle);
+        colliders.remove(collider_handle, &mut bodies, true);
+    }
+
+    let (removed, deleted) = joint_handles.take_removed_and_deleted();
+    let removed_joints = removed
+        .into_iter()
+        .filter(|entity| !joint_handles.contains(*entity))
+        .filter_map(|entity| entity_maps.joint_handle(entity));
+    let deleted_joints = deleted
+        .into_iter()
+        .map(|(_, joint_handle)| joint_handle.handle);
+    for joint_handle in removed_joints.chain(deleted_joints).collect::<Vec<_>>() {
+        entity_maps.remove_joint(joint_handle);
+        joints.remove(joint_handle, &mut bodies, true);
+    }
+
+    if configuration.delete_broken_joint_entities {
+        for entity in broken_joint_entities {
+            entities.delete_unchecked(entity);
+        }
     }
-    for (_, joint_handle) in joint_handles.take_deleted().iter() {
-        joints.remove(joint_handle.handle, &mut bodies, true);
+}
+
+/// Deletes the component of `entity` from `storage`.
+///
+/// shipyard 0.5.0 reads past the end of the storage when deleting its last component, in
+/// `SparseSet::actual_remove`, which aborts debug builds since Rust 1.78 checks
+/// `get_unchecked`, see <https://github.com/leudz/shipyard/issues>. The storage is cleared
+/// instead when it only holds this component. When the component is the last of several,
+/// the storage is drained and refilled in the same order except for the last two
+/// components, so that deleting this one moves the other back to its place.
+///
+/// Refilling the storage resets the insertion and modification flags of its components, and
+/// draining it reports them as removed: readers of the removed components have to check
+/// whether they are still there, like `destroy_body_and_collider_system` does.
+pub(crate) fn delete_component<T: 'static>(storage: &mut ViewMut<T>, entity: EntityId) -> bool {
+    let index = match storage.index_of(entity) {
+        Some(index) => index,
+        None => return false,
+    };
+    let len = storage.len();
+    if len == 1 {
+        storage.clear();
+        return true;
+    } else if index == len - 1 {
+        let mut components = storage.drain().with_id().collect::<Vec<_>>();
+        components.swap(len - 2, len - 1);
+        for (entity, component) in components {
+            storage.add_component_unchecked(entity, component);
+        }
     }
+    storage.delete(entity)
+}
+
+#[test]
+fn test_delete_component() {
+    use shipyard::*;
+
+    let mut world = World::new();
+    let entities = (0..4u32)
+        .map(|i| world.add_entity((i,)))
+        .collect::<Vec<_>>();
+    let mut storage = world.borrow::<ViewMut<u32>>().unwrap();
+    storage.track_deletion();
+
+    // the other components keep their order when the last one is deleted
+    assert!(delete_component(&mut storage, entities[3]));
+    assert_eq!(storage.as_slice(), &[0, 1, 2]);
+    assert!(!delete_component(&mut storage, entities[3]));
+    assert!(delete_component(&mut storage, entities[0]));
+    assert_eq!(storage.get(entities[1]), Ok(&1));
+    assert_eq!(storage.get(entities[2]), Ok(&2));
+    assert!(delete_component(&mut storage, entities[1]));
+    assert_eq!(storage.get(entities[2]), Ok(&2));
+    assert!(delete_component(&mut storage, entities[2]));
+    assert!(storage.is_empty());
+    assert_eq!(storage.deleted().len(), 4);
+}
+
+#[test]
+fn test_destroy_broken_joints() {
+    use rapier::dynamics::BallJoint;
+    use rapier::math::Point;
+    use shipyard::*;
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+
+    let entity1 = world.add_entity((RigidBodyBuilder::new_static(),));
+    let entity2 = world.add_entity((RigidBodyBuilder::new_dynamic(),));
+    let entity3 = world.add_entity((RigidBodyBuilder::new_dynamic(),));
+    let joint = BallJoint::new(Point::origin(), Point::origin());
+    let joint12 = world.add_entity((JointBuilderComponent::new(joint, entity1, entity2),));
+    let joint23 = world.add_entity((JointBuilderComponent::new(joint, entity2, entity3),));
+    world.run(create_body_and_collider_system).unwrap();
+    world.run(create_joints_system).unwrap();
+    let joint12_handle = world
+        .borrow::<UniqueView<EntityMaps>>()
+        .unwrap()
+        .joint_handle(joint12)
+        .unwrap();
+
+    // the joint entity loses its handle
+    world.delete_entity(entity1);
+    world.run(destroy_body_and_collider_system).unwrap();
+    {
+        let (joints, joint_handles, entity_maps, events) = world
+            .borrow::<(
+                UniqueView<JointSet>,
+                View<JointHandleComponent>,
+                UniqueView<EntityMaps>,
+                UniqueView<EventQueue>,
+            )>()
+            .unwrap();
+        assert!(joint_handles.get(joint12).is_err());
+        assert!(joint_handles.get(joint23).is_ok());
+        assert_eq!(joints.len(), 1);
+        assert_eq!(entity_maps.joint_handle(joint12), None);
+        assert_eq!(entity_maps.joint_entity(joint12_handle), None);
+        assert_eq!(
+            events.broken_joints.pop(),
+            Ok(JointBrokenEvent {
+                joint: joint12,
+                entity1,
+                entity2,
+            })
+        );
+    }
+    assert!(world.borrow::<EntitiesView>().unwrap().is_alive(joint12));
+
+    // the joint entity is deleted
+    world
+        .borrow::<UniqueViewMut<RapierConfiguration>>()
+        .unwrap()
+        .delete_broken_joint_entities = true;
+    world.delete_entity(entity3);
+    world.run(destroy_body_and_collider_system).unwrap();
+    assert!(!world.borrow::<EntitiesView>().unwrap().is_alive(joint23));
+    assert_eq!(world.borrow::<UniqueView<JointSet>>().unwrap().len(), 0);
+}
+
+#[test]
+fn test_destroy_removed_handle_components() {
+    use rapier::dynamics::BallJoint;
+    use rapier::math::Point;
+    use shipyard::*;
+
+    let mut world = World::new();
+    world.run(setup_physics).unwrap();
+
+    let entity1 = world.add_entity((RigidBodyBuilder::new_dynamic(), ColliderBuilder::ball(0.5)));
+    let entity2 = world.add_entity((RigidBodyBuilder::new_dynamic(), ColliderBuilder::ball(0.5)));
+    let child = world.add_entity((ColliderBuilder::ball(0.5), ColliderParent(entity1)));
+    let joint = BallJoint::new(Point::origin(), Point::origin());
+    let joint12 = world.add_entity((JointBuilderComponent::new(joint, entity1, entity2),));
+    // removing the last component of a storage hits a shipyard bug, see `delete_component`
+    world.add_entity((JointBuilderComponent::new(joint, entity2, entity1),));
+    world.run(create_body_and_collider_system).unwrap();
+    world.run(create_joints_system).unwrap();
+
+    // removing a joint handle removes the joint
+    world
+        .borrow::<ViewMut<JointHandleComponent>>()
+        .unwrap()
+        .remove(joint12);
+    world.run(destroy_body_and_collider_system).unwrap();
+    {
+        let (joints, entity_maps) = world
+            .borrow::<(UniqueView<JointSet>, UniqueView<EntityMaps>)>()
+            .unwrap();
+        assert_eq!(joints.len(), 1);
+        assert_eq!(entity_maps.joint_handle(joint12), None);
+    }
+
+    // removing a collider handle removes the collider but keeps the body
+    world
+        .borrow::<ViewMut<ColliderHandleComponent>>()
+        .unwrap()
+        .remove(entity2);
+    world.run(destroy_body_and_collider_system).unwrap();
+    {
+        let (bodies, colliders, entity_maps) = world
+            .borrow::<(
+                UniqueView<RigidBodySet>,
+                UniqueView<ColliderSet>,
+                UniqueView<EntityMaps>,
+            )>()
+            .unwrap();
+        assert_eq!(colliders.len(), 2);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(entity_maps.collider_handle(entity2), None);
+    }
+
+    // removing a body handle removes its colliders, including child colliders
+    world
+        .borrow::<ViewMut<RigidBodyHandleComponent>>()
+        .unwrap()
+        .remove(entity1);
+    world.run(destroy_body_and_collider_system).unwrap();
+    let (bodies, colliders, entity_maps, collider_handles) = world
+        .borrow::<(
+            UniqueView<RigidBodySet>,
+            UniqueView<ColliderSet>,
+            UniqueView<EntityMaps>,
+            View<ColliderHandleComponent>,
+        )>()
+        .unwrap();
+    assert_eq!(bodies.len(), 1);
+    assert_eq!(colliders.len(), 0);
+    assert_eq!(entity_maps.body_handle(entity1), None);
+    assert_eq!(entity_maps.collider_handle(child), None);
+    assert!(collider_handles.get(entity1).is_err());
+    assert!(collider_handles.get(child).is_err());
 }
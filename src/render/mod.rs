@@ -1,36 +1,30 @@
-use crate::physics::{ColliderHandleComponent, RapierConfiguration};
+#[cfg(feature = "dim2")]
+use crate::debug_draw::draw_colliders;
+use crate::debug_draw::{draw_contacts, draw_joints, draw_overlays, DebugColor, DebugDrawBackend};
+#[cfg(feature = "dim3")]
+use crate::debug_draw::{shape_mesh, visit_colliders};
+use crate::physics::{
+    ColliderHandleComponent, EntityMaps, InterpolatedTransform, PhysicsMetrics,
+    RapierConfiguration, RollingStat,
+};
 use macroquad::prelude::*;
-use rapier::dynamics::RigidBodySet;
-use rapier::geometry::{Collider, ColliderSet, ShapeType};
+use rapier::dynamics::{IntegrationParameters, JointSet, RigidBodySet};
+#[cfg(feature = "dim3")]
+use rapier::geometry::{ColliderHandle, Segment, Shape, ShapeType};
+use rapier::geometry::{ColliderSet, NarrowPhase};
+#[cfg(feature = "dim3")]
+use rapier::math::Isometry;
+use rapier::math::Point;
 use rapier::pipeline::PhysicsPipeline;
-use shipyard::{Get, IntoIter, IntoWithId, UniqueView, View};
+#[cfg(feature = "dim3")]
+use shipyard::UniqueViewMut;
+use shipyard::{UniqueView, View};
+#[cfg(feature = "dim3")]
 use std::collections::HashMap;
 
-/// The desired render color of a Rapier collider.
-pub struct RapierRenderColor(pub f32, pub f32, pub f32);
-
-const PALLETE: [Color; 3] = [
-    Color::new(
-        0x98 as f32 / 255.0,
-        0xC1 as f32 / 255.0,
-        0xD9 as f32 / 255.0,
-        1.0,
-    ),
-    Color::new(
-        0x05 as f32 / 255.0,
-        0x3C as f32 / 255.0,
-        0x5E as f32 / 255.0,
-        1.0,
-    ),
-    Color::new(
-        0x1F as f32 / 255.0,
-        0x7A as f32 / 255.0,
-        0x8C as f32 / 255.0,
-        1.0,
-    ),
-];
-
-#[allow(dead_code)]
+pub use crate::debug_draw::{DebugRenderSettings, RapierRenderColor};
+
+#[cfg(feature = "dim3")]
 const WIRE_COLOR: Color = Color::new(
     0x0e as f32 / 255.0,
     0x2c as f32 / 255.0,
@@ -38,12 +32,11 @@ const WIRE_COLOR: Color = Color::new(
     1.0,
 );
 
-const GROUND_COLOR: Color = Color::new(
-    0xF3 as f32 / 255.0,
-    0xD9 as f32 / 255.0,
-    0xB1 as f32 / 255.0,
-    1.0,
-);
+impl From<DebugColor> for Color {
+    fn from(color: DebugColor) -> Self {
+        Color::new(color.r, color.g, color.b, color.a)
+    }
+}
 
 /// Render the physics time and the total frame time in the screen.
 pub fn render_physics_stats(pipeline: UniqueView<PhysicsPipeline>) {
@@ -55,88 +48,571 @@ pub fn render_physics_stats(pipeline: UniqueView<PhysicsPipeline>) {
     draw_text(&fps, 10.0, 50.0, 30.0, BLACK);
 }
 
+/// Width, in pixels, of the panel drawn by `render_physics_metrics`.
+const METRICS_PANEL_WIDTH: f32 = 360.0;
+
+/// Height, in pixels, of the frame time graph.
+const METRICS_GRAPH_HEIGHT: f32 = 60.0;
+
+/// Height, in pixels, of a line of text of the panel.
+const METRICS_LINE_HEIGHT: f32 = 16.0;
+
+/// Space, in pixels, around the content of the panel.
+const METRICS_PADDING: f32 = 8.0;
+
+/// System responsible for rendering the `PhysicsMetrics` in a panel at the top right
+/// of the screen.
+///
+/// The panel shows the timings of the last frame with their average, min and max, a graph
+/// of the frame time in blue and of the step time in red, and the size of the physics world.
+/// It is drawn in screen coordinates, after `set_default_camera`, and requires the metrics
+/// to be enabled with `PhysicsSetup::metrics`.
+pub fn render_physics_metrics(metrics: UniqueView<PhysicsMetrics>) {
+    let timings = [
+        ("Frame", &metrics.frame_time),
+        ("Step", &metrics.step_time),
+        ("Broad-phase", &metrics.broad_phase_time),
+        ("Narrow-phase", &metrics.narrow_phase_time),
+        ("Islands", &metrics.island_construction_time),
+        ("Solver", &metrics.solver_time),
+        ("CCD", &metrics.ccd_time),
+    ];
+    let counts = [
+        format!(
+            "Bodies: {} ({} active, {} sleeping)",
+            metrics.bodies, metrics.active_bodies, metrics.sleeping_bodies
+        ),
+        format!(
+            "Colliders: {}, joints: {}",
+            metrics.colliders, metrics.joints
+        ),
+        format!(
+            "Contact pairs: {}, contacts: {}, substeps: {}",
+            metrics.contact_pairs, metrics.contacts, metrics.substeps
+        ),
+    ];
+
+    let lines = 1 + timings.len() + counts.len();
+    let height = lines as f32 * METRICS_LINE_HEIGHT + METRICS_GRAPH_HEIGHT + METRICS_PADDING * 4.0;
+    let x = screen_width() - METRICS_PANEL_WIDTH - 10.0;
+    let mut y = 10.0 + METRICS_PADDING;
+    draw_rectangle(
+        x,
+        10.0,
+        METRICS_PANEL_WIDTH,
+        height,
+        Color::new(1.0, 1.0, 1.0, 0.8),
+    );
+
+    let x = x + METRICS_PADDING;
+    draw_panel_line("Physics (ms): last, average, min, max", x, &mut y);
+    for (name, stat) in timings.iter() {
+        draw_panel_line(
+            &format!(
+                "{}: {:.2}, {:.2}, {:.2}, {:.2}",
+                name,
+                stat.latest(),
+                stat.average(),
+                stat.min(),
+                stat.max()
+            ),
+            x,
+            &mut y,
+        );
+    }
+
+    y += METRICS_PADDING;
+    let graph_width = METRICS_PANEL_WIDTH - METRICS_PADDING * 2.0;
+    draw_rectangle_lines(x, y, graph_width, METRICS_GRAPH_HEIGHT, 1.0, GRAY);
+    let max_time = metrics.frame_time.max().max(metrics.step_time.max());
+    draw_time_graph(
+        &metrics.frame_time,
+        metrics.window(),
+        max_time,
+        vec2(x, y),
+        graph_width,
+        BLUE,
+    );
+    draw_time_graph(
+        &metrics.step_time,
+        metrics.window(),
+        max_time,
+        vec2(x, y),
+        graph_width,
+        RED,
+    );
+    y += METRICS_GRAPH_HEIGHT + METRICS_PADDING;
+
+    for text in counts.iter() {
+        draw_panel_line(text, x, &mut y);
+    }
+}
+
+/// Draws a line of text of the metrics panel below `y`, and moves `y` to its baseline.
+fn draw_panel_line(text: &str, x: f32, y: &mut f32) {
+    *y += METRICS_LINE_HEIGHT;
+    draw_text(text, x, *y, METRICS_LINE_HEIGHT + 4.0, BLACK);
+}
+
+/// Draws the samples of a timing as a line, in the graph whose top left corner is `origin`.
+fn draw_time_graph(
+    stat: &RollingStat,
+    window: usize,
+    max_time: f32,
+    origin: Vec2,
+    width: f32,
+    color: Color,
+) {
+    if max_time <= 0.0 {
+        return;
+    }
+    let step = width / window.saturating_sub(1).max(1) as f32;
+    let point = |i: usize, time: f32| {
+        vec2(
+            origin.x + i as f32 * step,
+            origin.y + METRICS_GRAPH_HEIGHT * (1.0 - time / max_time),
+        )
+    };
+    let samples: Vec<_> = stat.samples().collect();
+    for (i, times) in samples.windows(2).enumerate() {
+        let (a, b) = (point(i, times[0]), point(i + 1, times[1]));
+        draw_line(a.x, a.y, b.x, b.y, 1.0, color);
+    }
+}
+
+/// The `DebugDrawBackend` drawing with macroquad, `scale` pixels per Rapier unit.
+///
+/// In 2D, the y axis of the screen points down, so the drawing is flipped vertically.
+/// In 3D, the text labels are not drawn, and the polygons are batched until the batch
+/// is full, `flush` is called or the backend is dropped.
+pub struct MacroquadBackend {
+    scale: f32,
+    #[cfg(feature = "dim3")]
+    polygons: PolygonBatch,
+}
+
+impl MacroquadBackend {
+    /// Creates a backend drawing `scale` pixels per Rapier unit.
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale,
+            #[cfg(feature = "dim3")]
+            polygons: PolygonBatch::default(),
+        }
+    }
+
+    /// Draws the polygons batched so far. In 2D, everything is drawn right away.
+    pub fn flush(&mut self) {
+        #[cfg(feature = "dim3")]
+        if !self.polygons.indices.is_empty() {
+            let gl = unsafe { get_internal_gl().quad_gl };
+            gl.texture(None);
+            gl.draw_mode(DrawMode::Triangles);
+            gl.geometry(&self.polygons.vertices, &self.polygons.indices);
+            self.polygons.vertices.clear();
+            self.polygons.indices.clear();
+        }
+    }
+
+    #[cfg(feature = "dim2")]
+    fn to_screen(&self, point: &Point<f32>) -> Vec2 {
+        vec2(point.x * self.scale, -point.y * self.scale)
+    }
+
+    #[cfg(feature = "dim3")]
+    fn to_screen(&self, point: &Point<f32>) -> Vec3 {
+        to_vec3(point) * self.scale
+    }
+}
+
+impl Drop for MacroquadBackend {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Polygons waiting to be drawn by a single `QuadGl::geometry` call.
+#[cfg(feature = "dim3")]
+#[derive(Default)]
+struct PolygonBatch {
+    vertices: Vec<([f32; 3], [f32; 2], [f32; 4])>,
+    indices: Vec<u16>,
+}
+
+/// Thickness, in pixels, of the lines drawn in 2D.
+#[cfg(feature = "dim2")]
+const LINE_THICKNESS: f32 = 2.0;
+
+/// Radius, in pixels in 2D and in Rapier units in 3D, of the debug points.
+#[cfg(feature = "dim2")]
+const DEBUG_POINT_RADIUS: f32 = 3.0;
+#[cfg(feature = "dim3")]
+const DEBUG_POINT_RADIUS: f32 = 0.05;
+
+#[cfg(feature = "dim2")]
+impl DebugDrawBackend for MacroquadBackend {
+    fn draw_line(&mut self, a: &Point<f32>, b: &Point<f32>, color: DebugColor) {
+        let (a, b) = (self.to_screen(a), self.to_screen(b));
+        draw_line(a.x, a.y, b.x, b.y, LINE_THICKNESS, color.into());
+    }
+
+    fn draw_polygon(&mut self, points: &[Point<f32>], color: DebugColor) {
+        for i in 1..points.len().saturating_sub(1) {
+            draw_triangle(
+                self.to_screen(&points[0]),
+                self.to_screen(&points[i]),
+                self.to_screen(&points[i + 1]),
+                color.into(),
+            );
+        }
+    }
+
+    fn draw_circle(&mut self, center: &Point<f32>, radius: f32, color: DebugColor) {
+        let center = self.to_screen(center);
+        draw_circle(center.x, center.y, radius * self.scale, color.into());
+    }
+
+    fn draw_point(&mut self, point: &Point<f32>, color: DebugColor) {
+        let point = self.to_screen(point);
+        draw_circle(point.x, point.y, DEBUG_POINT_RADIUS, color.into());
+    }
+
+    fn draw_text(&mut self, position: &Point<f32>, text: &str, color: DebugColor) {
+        let position = self.to_screen(position);
+        draw_text(text, position.x, position.y, 20.0, color.into());
+    }
+}
+
+#[cfg(feature = "dim3")]
+impl DebugDrawBackend for MacroquadBackend {
+    fn draw_line(&mut self, a: &Point<f32>, b: &Point<f32>, color: DebugColor) {
+        draw_line_3d(self.to_screen(a), self.to_screen(b), color.into());
+    }
+
+    fn draw_polygon(&mut self, points: &[Point<f32>], color: DebugColor) {
+        let color: [f32; 4] = Color::from(color).into();
+        let scale = self.scale;
+        let vertex = |point: &Point<f32>| ((to_vec3(point) * scale).into(), [0.0, 0.0], color);
+
+        // The polygon is drawn as fans of triangles sharing its first point, small enough
+        // to fit in a batch.
+        let max_triangles = MAX_BATCH_INDICES / 3;
+        for start in (1..points.len().saturating_sub(1)).step_by(max_triangles) {
+            let fan = &points[start..(start + max_triangles + 1).min(points.len())];
+            if self.polygons.indices.len() + (fan.len() - 1) * 3 > MAX_BATCH_INDICES {
+                self.flush();
+            }
+
+            let first = self.polygons.vertices.len() as u16;
+            self.polygons.vertices.push(vertex(&points[0]));
+            self.polygons.vertices.extend(fan.iter().map(vertex));
+            self.polygons
+                .indices
+                .extend((1..fan.len() as u16).flat_map(|i| [first, first + i, first + i + 1]));
+        }
+    }
+
+    fn draw_circle(&mut self, center: &Point<f32>, radius: f32, color: DebugColor) {
+        draw_sphere(
+            self.to_screen(center),
+            radius * self.scale,
+            None,
+            color.into(),
+        );
+    }
+
+    fn draw_point(&mut self, point: &Point<f32>, color: DebugColor) {
+        draw_sphere(
+            self.to_screen(point),
+            DEBUG_POINT_RADIUS * self.scale,
+            None,
+            color.into(),
+        );
+    }
+
+    fn draw_text(&mut self, _position: &Point<f32>, _text: &str, _color: DebugColor) {}
+}
+
 /// System responsible for rendering the colliders with the macroquad rendering crate.
+///
+/// Colliders attached to a rigid-body with an `InterpolatedTransform` are drawn at
+/// their interpolated position. In 3D, the meshes are kept in the `ColliderMeshCache`
+/// resource if there is one, or else computed again every frame.
+///
+/// Colliders without a `RapierRenderColor` are colored by rigid-body, or by the state of
+/// their rigid-body when `DebugRenderSettings::body_states` is enabled.
+#[allow(clippy::too_many_arguments)]
 pub fn render_colliders(
     configuration: UniqueView<RapierConfiguration>,
     bodies: UniqueView<RigidBodySet>,
     colliders: UniqueView<ColliderSet>,
+    entity_maps: UniqueView<EntityMaps>,
     colliders_handles: View<ColliderHandleComponent>,
     debug_colors: View<RapierRenderColor>,
+    interpolated_transforms: View<InterpolatedTransform>,
+    settings: Option<UniqueView<DebugRenderSettings>>,
+    #[cfg(feature = "dim3")] mut mesh_cache: Option<UniqueViewMut<ColliderMeshCache>>,
 ) {
-    let mut icolor = 0;
-    let mut body_colors = HashMap::new();
-
-    let gl = unsafe { get_internal_gl().quad_gl };
-
-    for (entity, collider) in colliders_handles.iter().with_id() {
-        if let Some(collider) = colliders.get(collider.handle()) {
-            if let Some(body) = bodies.get(collider.parent()) {
-                let default_color = if body.is_static() {
-                    GROUND_COLOR
-                } else {
-                    *body_colors.entry(collider.parent()).or_insert_with(|| {
-                        icolor += 1;
-                        PALLETE[icolor % PALLETE.len()]
-                    })
-                };
+    let settings = settings.map(|settings| *settings).unwrap_or_default();
 
-                let debug_color = debug_colors.get(entity).ok();
-
-                let color = debug_color
-                    .map(|c| Color::new(c.0, c.1, c.2, 1.0))
-                    .unwrap_or(default_color);
+    #[cfg(feature = "dim2")]
+    draw_colliders(
+        &mut MacroquadBackend::new(configuration.scale),
+        &bodies,
+        &colliders,
+        &entity_maps,
+        &colliders_handles,
+        &debug_colors,
+        &interpolated_transforms,
+        &settings,
+        configuration.scale,
+    );
 
-                render_colider(collider, color, configuration.scale, gl);
+    #[cfg(feature = "dim3")]
+    {
+        let mut frame_cache = ColliderMeshCache::default();
+        let mesh_cache = match &mut mesh_cache {
+            Some(mesh_cache) => {
+                mesh_cache
+                    .meshes
+                    .retain(|(handle, _), _| colliders.get(*handle).is_some());
+                &mut **mesh_cache
             }
-        }
+            None => &mut frame_cache,
+        };
+
+        let gl = unsafe { get_internal_gl().quad_gl };
+        visit_colliders(
+            &bodies,
+            &colliders,
+            &entity_maps,
+            &colliders_handles,
+            &debug_colors,
+            &interpolated_transforms,
+            settings.body_states,
+            configuration.scale,
+            |handle, collider, position, color| {
+                render_shape(
+                    collider.shape(),
+                    position,
+                    color.into(),
+                    configuration.scale,
+                    (handle, 0),
+                    mesh_cache,
+                    gl,
+                )
+            },
+        );
     }
 }
 
-#[cfg(feature = "dim2")]
-fn render_colider(collider: &Collider, color: Color, scale: f32, gl: &mut QuadGl) {
-    let pos = collider.position();
-    let shape = collider.shape();
+/// System responsible for rendering the AABBs of the colliders, and the velocities and
+/// centers of mass of the rigid-bodies, as enabled by the `DebugRenderSettings`.
+///
+/// The velocities are drawn from the center of mass, as the motion of the rigid-body
+/// during `DebugRenderSettings::velocity_scale` seconds. Static rigid-bodies are skipped.
+pub fn render_debug_overlays(
+    settings: UniqueView<DebugRenderSettings>,
+    configuration: UniqueView<RapierConfiguration>,
+    bodies: UniqueView<RigidBodySet>,
+    colliders: UniqueView<ColliderSet>,
+) {
+    draw_overlays(
+        &mut MacroquadBackend::new(configuration.scale),
+        &settings,
+        &bodies,
+        &colliders,
+    );
+}
 
-    let translation =
-        glam::Vec3::new(pos.translation.vector.x, -pos.translation.vector.y, 0.0) * scale;
-    match shape.shape_type() {
-        ShapeType::Cuboid => {
-            let c = shape.as_cuboid().unwrap();
-            gl.push_model_matrix(glam::Mat4::from_translation(translation));
-            gl.push_model_matrix(glam::Mat4::from_rotation_z(-pos.rotation.angle()));
-            gl.push_model_matrix(glam::Mat4::from_scale(Vec3::one() * scale));
-
-            draw_rectangle(
-                -c.half_extents.x,
-                -c.half_extents.y,
-                c.half_extents.x * 2.0,
-                c.half_extents.y * 2.0,
-                color,
-            );
-            gl.pop_model_matrix();
-            gl.pop_model_matrix();
-            gl.pop_model_matrix();
-        }
-        ShapeType::Ball => {
-            let b = shape.as_ball().unwrap();
-            gl.push_model_matrix(glam::Mat4::from_translation(translation));
-            gl.push_model_matrix(glam::Mat4::from_rotation_z(-pos.rotation.angle()));
-            gl.push_model_matrix(glam::Mat4::from_scale(Vec3::one() * scale));
+/// System responsible for rendering the contacts of the narrow-phase, when enabled
+/// by the `DebugRenderSettings`.
+///
+/// Only the contacts seen by the constraints solver are drawn. Each contact point is drawn
+/// with the contact normal, whose length is proportional to the impulse applied by the
+/// last step, and the penetration depth along the normal.
+pub fn render_contacts(
+    settings: UniqueView<DebugRenderSettings>,
+    configuration: UniqueView<RapierConfiguration>,
+    integration_parameters: UniqueView<IntegrationParameters>,
+    colliders: UniqueView<ColliderSet>,
+    narrow_phase: UniqueView<NarrowPhase>,
+) {
+    draw_contacts(
+        &mut MacroquadBackend::new(configuration.scale),
+        &settings,
+        &narrow_phase,
+        &colliders,
+        integration_parameters.prediction_distance,
+    );
+}
+
+/// System responsible for rendering the joints of the `JointSet`, when enabled by the
+/// `DebugRenderSettings`.
+///
+/// Each joint is drawn with lines from its rigid-bodies to their anchor, and between the
+/// anchors. Fixed joints show the axes of their frame, prismatic joints their axis and
+/// limits and, in 3D, revolute joints their axis and the angle between their bases, as
+/// Rapier joints have no angular limits. Joints whose anchors drifted apart, or attached
+/// to a removed rigid-body, are highlighted.
+pub fn render_joints(
+    settings: UniqueView<DebugRenderSettings>,
+    configuration: UniqueView<RapierConfiguration>,
+    bodies: UniqueView<RigidBodySet>,
+    joints: UniqueView<JointSet>,
+    entity_maps: UniqueView<EntityMaps>,
+    interpolated_transforms: View<InterpolatedTransform>,
+) {
+    draw_joints(
+        &mut MacroquadBackend::new(configuration.scale),
+        &settings,
+        &bodies,
+        &joints,
+        &entity_maps,
+        &interpolated_transforms,
+        configuration.scale,
+    );
+}
+
+/// Geometry of the colliders drawn as triangle meshes, cached by `render_colliders`
+/// so that static meshes, like terrains, are not computed again every frame.
+///
+/// Without it, `render_colliders` computes the meshes again every frame. The meshes of
+/// removed colliders are dropped on the next render.
+#[cfg(feature = "dim3")]
+#[derive(Default)]
+pub struct ColliderMeshCache {
+    meshes: HashMap<(ColliderHandle, usize), Vec<MeshBatch>>,
+}
+
+#[cfg(feature = "dim3")]
+impl ColliderMeshCache {
+    /// Number of meshes in the cache.
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+
+    /// Removes every mesh from the cache.
+    pub fn clear(&mut self) {
+        self.meshes.clear();
+    }
+}
+
+/// Maximum number of indices drawn by a single `QuadGl::geometry` call.
+#[cfg(feature = "dim3")]
+const MAX_BATCH_INDICES: usize = 5000;
 
-            draw_circle(0.0, 0.0, b.radius, color);
+/// Maximum number of triangles of a mesh drawn by a single `QuadGl::geometry` call, the
+/// edges taking 6 indices per triangle.
+#[cfg(feature = "dim3")]
+const MAX_BATCH_TRIANGLES: usize = MAX_BATCH_INDICES / 6;
+
+/// Part of a mesh small enough to be drawn by a single `QuadGl::geometry` call.
+#[cfg(feature = "dim3")]
+struct MeshBatch {
+    vertices: Vec<([f32; 3], [f32; 2], [f32; 4])>,
+    wire_vertices: Vec<([f32; 3], [f32; 2], [f32; 4])>,
+    triangles: Vec<u16>,
+    edges: Vec<u16>,
+}
+
+#[cfg(feature = "dim3")]
+impl MeshBatch {
+    /// Splits a mesh in batches, each batch indexing its own copy of the vertices it uses.
+    fn split(vertices: &[Point<f32>], indices: &[[u32; 3]]) -> Vec<MeshBatch> {
+        const NONE: u16 = u16::MAX;
+        let mut batch_indices = vec![NONE; vertices.len()];
+
+        indices
+            .chunks(MAX_BATCH_TRIANGLES)
+            .map(|chunk| {
+                let mut batch = MeshBatch {
+                    vertices: Vec::new(),
+                    wire_vertices: Vec::new(),
+                    triangles: Vec::with_capacity(chunk.len() * 3),
+                    edges: Vec::with_capacity(chunk.len() * 6),
+                };
+                let mut used = Vec::new();
+                for triangle in chunk {
+                    let [a, b, c] = triangle.map(|i| {
+                        let i = i as usize;
+                        if batch_indices[i] == NONE {
+                            batch_indices[i] = batch.vertices.len() as u16;
+                            let v = vertices[i];
+                            batch.vertices.push(([v.x, v.y, v.z], [0.0, 0.0], [1.0; 4]));
+                            used.push(i);
+                        }
+                        batch_indices[i]
+                    });
+                    batch.triangles.extend_from_slice(&[a, b, c]);
+                    batch.edges.extend_from_slice(&[a, b, b, c, c, a]);
+                }
+                for i in used {
+                    batch_indices[i] = NONE;
+                }
+                batch.wire_vertices = batch
+                    .vertices
+                    .iter()
+                    .map(|(position, uv, _)| (*position, *uv, WIRE_COLOR.into()))
+                    .collect();
+                batch
+            })
+            .collect()
+    }
 
-            gl.pop_model_matrix();
-            gl.pop_model_matrix();
-            gl.pop_model_matrix();
+    fn draw(&mut self, color: Color, gl: &mut QuadGl) {
+        let color: [f32; 4] = color.into();
+        if self
+            .vertices
+            .first()
+            .map_or(false, |vertex| vertex.2 != color)
+        {
+            for vertex in &mut self.vertices {
+                vertex.2 = color;
+            }
         }
-        _ => {}
-    };
+
+        gl.texture(None);
+        gl.draw_mode(DrawMode::Triangles);
+        gl.geometry(&self.vertices, &self.triangles);
+        gl.draw_mode(DrawMode::Lines);
+        gl.geometry(&self.wire_vertices, &self.edges);
+        gl.draw_mode(DrawMode::Triangles);
+    }
 }
 
+/// Renders a shape at the given position, in Rapier units. Compound shapes are
+/// rendered by recursing into their sub-shapes, identified in the cache by their index.
 #[cfg(feature = "dim3")]
-fn render_colider(collider: &Collider, color: Color, scale: f32, gl: &mut QuadGl) {
-    let pos = collider.position();
-    let shape = collider.shape();
+fn render_shape(
+    shape: &dyn Shape,
+    pos: &Isometry<f32>,
+    color: Color,
+    scale: f32,
+    cache_key: (ColliderHandle, usize),
+    mesh_cache: &mut ColliderMeshCache,
+    gl: &mut QuadGl,
+) {
+    if let Some(compound) = shape.as_compound() {
+        for (i, (sub_pos, sub_shape)) in compound.shapes().iter().enumerate() {
+            render_shape(
+                &**sub_shape,
+                &(pos * sub_pos),
+                color,
+                scale,
+                (cache_key.0, i),
+                mesh_cache,
+                gl,
+            );
+        }
+        return;
+    }
+
     let translation = glam::Vec3::new(
         pos.translation.vector.x,
         pos.translation.vector.y,
@@ -156,6 +632,10 @@ fn render_colider(collider: &Collider, color: Color, scale: f32, gl: &mut QuadGl
     ));
 
     match shape.shape_type() {
+        ShapeType::Ball => {
+            let b = shape.as_ball().unwrap();
+            draw_sphere(Vec3::zero(), b.radius, None, color);
+        }
         ShapeType::Cuboid => {
             let c = shape.as_cuboid().unwrap();
             let size = Vec3::new(c.half_extents.x, c.half_extents.y, c.half_extents.z) * 2.0;
@@ -163,38 +643,64 @@ fn render_colider(collider: &Collider, color: Color, scale: f32, gl: &mut QuadGl
             draw_cube(Vec3::zero(), size, None, color);
             draw_cube_wires(Vec3::zero(), size, WIRE_COLOR);
         }
-        ShapeType::Ball => {
-            let b = shape.as_ball().unwrap();
-            let radius = b.radius * scale;
-
-            draw_sphere(Vec3::zero(), radius, None, color);
+        ShapeType::Segment => {
+            let s = shape.as_shape::<Segment>().unwrap();
+            draw_line_3d(to_vec3(&s.a), to_vec3(&s.b), color);
         }
-        ShapeType::TriMesh => {
-            let t = shape.as_trimesh().unwrap();
-            let tris: Vec<([f32; 3], [f32; 2], [f32; 4])> = t
-                .vertices()
-                .iter()
-                .enumerate()
-                .map(|(i, v)| {
-                    let uv: [f32; 2] = match i % 3 {
-                        0 => [1., 0.],
-                        1 => [0., 1.],
-                        _ => [0., 0.],
-                    };
-
-                    ([v.x, v.y, v.z], uv, color.into())
-                })
-                .collect();
-
-            gl.draw_mode(DrawMode::Lines);
-            let indices = t
-                .flat_indices()
-                .iter()
-                .map(|x| *x as u16)
-                .collect::<Vec<u16>>();
-            gl.geometry(&tris[..], &indices);
+        ShapeType::Polyline => {
+            let p = shape.as_polyline().unwrap();
+            for segment in p.segments() {
+                draw_line_3d(to_vec3(&segment.a), to_vec3(&segment.b), color);
+            }
+        }
+        _ => {
+            let batches = mesh_cache.meshes.entry(cache_key).or_insert_with(|| {
+                let (vertices, indices) = shape_mesh(shape);
+                MeshBatch::split(&vertices, &indices)
+            });
+            for batch in batches {
+                batch.draw(color, gl);
+            }
         }
-        _ => {}
     }
     gl.pop_model_matrix();
 }
+
+#[cfg(feature = "dim3")]
+fn to_vec3(point: &Point<f32>) -> Vec3 {
+    Vec3::new(point.x, point.y, point.z)
+}
+
+#[cfg(feature = "dim3")]
+#[test]
+fn test_mesh_batches() {
+    // a strip of about 35 000 quads, more than the 65 535 vertices a u16 can index
+    let vertices: Vec<_> = (0..70_000)
+        .map(|i| Point::new((i / 2) as f32, (i % 2) as f32, 0.0))
+        .collect();
+    let indices: Vec<[u32; 3]> = (0..(vertices.len() as u32 - 2))
+        .map(|i| [i, i + 1, i + 2])
+        .collect();
+
+    let batches = MeshBatch::split(&vertices, &indices);
+    assert_eq!(
+        batches.len(),
+        (indices.len() + MAX_BATCH_TRIANGLES - 1) / MAX_BATCH_TRIANGLES
+    );
+
+    let mut triangles = 0;
+    for batch in &batches {
+        assert!(batch.triangles.len() <= 5000 && batch.edges.len() <= 5000);
+        assert!(batch.vertices.len() <= 10000);
+        for (i, triangle) in batch.triangles.chunks(3).enumerate() {
+            let expected = indices[triangles + i];
+            for (local, global) in triangle.iter().zip(&expected) {
+                let position = batch.vertices[*local as usize].0;
+                let vertex = vertices[*global as usize];
+                assert_eq!(position, [vertex.x, vertex.y, vertex.z]);
+            }
+        }
+        triangles += batch.triangles.len() / 3;
+    }
+    assert_eq!(triangles, indices.len());
+}